@@ -1,15 +1,23 @@
+mod loudness;
 mod reader;
 mod writer;
 
-use std::{fs::OpenOptions, path::PathBuf, sync::RwLock};
+use std::{collections::HashMap, fs::OpenOptions, path::PathBuf, sync::RwLock};
 
 use ytapi2::types::YoutubeMusicVideoRef;
 
 use crate::writer::write_video;
 
+/// Start of every valid cached audio file: the `ftyp` box of a DASH mp4
+pub const DASH_HEADER: [u8; 16] = [
+    0, 0, 0, 24, 102, 116, 121, 112, 100, 97, 115, 104, 0, 0, 0, 0,
+];
+
 pub struct YTLocalDatabase {
     cache_dir: PathBuf,
     references: RwLock<Vec<YoutubeMusicVideoRef>>,
+    /// Integrated loudness in LUFS of the tracks, by video id
+    loudness: RwLock<HashMap<String, f64>>,
 }
 
 impl YTLocalDatabase {
@@ -17,6 +25,7 @@ impl YTLocalDatabase {
         Self {
             cache_dir,
             references: RwLock::new(Vec::new()),
+            loudness: RwLock::new(HashMap::new()),
         }
     }
 
@@ -24,6 +33,11 @@ impl YTLocalDatabase {
         self.references.write().unwrap().clone_from(videos);
     }
 
+    /// Videos whose audio is in the cache
+    pub fn videos(&self) -> Vec<YoutubeMusicVideoRef> {
+        self.references.read().unwrap().clone()
+    }
+
     pub fn remove_video(&self, video: &YoutubeMusicVideoRef) {
         let mut database = self.references.write().unwrap();
         database.retain(|v| v.video_id != video.video_id);
@@ -0,0 +1,57 @@
+use std::{
+    fs::OpenOptions,
+    io::{Cursor, Read, Write},
+};
+
+use crate::{YTLocalDatabase, reader::read_str, writer::write_str};
+
+impl YTLocalDatabase {
+    /// Loads the loudness of the tracks from `loudness.bin`, the last value of a track wins
+    pub fn read_loudness(&self) -> Option<()> {
+        let mut buffer = Cursor::new(std::fs::read(self.cache_dir.join("loudness.bin")).ok()?);
+        let mut loudness = self.loudness.write().unwrap();
+        while buffer.get_mut().len() > buffer.position() as usize {
+            let video_id = read_str(&mut buffer)?;
+            let mut value = [0; 8];
+            buffer.read_exact(&mut value).ok()?;
+            loudness.insert(video_id, f64::from_le_bytes(value));
+        }
+        Some(())
+    }
+
+    pub fn get_loudness(&self, video_id: &str) -> Option<f64> {
+        self.loudness.read().unwrap().get(video_id).copied()
+    }
+
+    /// Stores the integrated loudness of a track in LUFS
+    pub fn set_loudness(&self, video_id: &str, lufs: f64) {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.cache_dir.join("loudness.bin"))
+            .unwrap();
+
+        write_str(&mut file, video_id);
+        file.write_all(&lufs.to_le_bytes()).unwrap();
+        self.loudness
+            .write()
+            .unwrap()
+            .insert(video_id.to_string(), lufs);
+    }
+
+    /// Loudness of the known tracks of an album, averaged on their energy
+    pub fn get_album_loudness(&self, album: &str) -> Option<f64> {
+        let references = self.references.read().unwrap();
+        let loudness = self.loudness.read().unwrap();
+        let energies = references
+            .iter()
+            .filter(|x| x.album == album)
+            .flat_map(|x| loudness.get(&x.video_id))
+            .map(|x| 10f64.powf(x / 10.))
+            .collect::<Vec<_>>();
+        if energies.is_empty() {
+            return None;
+        }
+        Some(10. * (energies.iter().sum::<f64>() / energies.len() as f64).log10())
+    }
+}
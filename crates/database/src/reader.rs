@@ -26,7 +26,7 @@ fn read_video(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
     })
 }
 
-fn read_str(cursor: &mut Cursor<Vec<u8>>) -> Option<String> {
+pub(crate) fn read_str(cursor: &mut Cursor<Vec<u8>>) -> Option<String> {
     let mut buf = vec![0u8; read_u32(cursor)? as usize];
     cursor.read_exact(&mut buf).ok()?;
     String::from_utf8(buf).ok()
@@ -2,7 +2,7 @@ use std::{fs::OpenOptions, io::Write};
 
 use ytapi2::types::YoutubeMusicVideoRef;
 
-use crate::YTLocalDatabase;
+use crate::{DASH_HEADER, YTLocalDatabase};
 use varuint::WriteVarint;
 
 impl YTLocalDatabase {
@@ -107,9 +107,7 @@ impl YTLocalDatabase {
                 }
             };
             // Check if the video file contains the header
-            if !video_file.starts_with(&[
-                0, 0, 0, 24, 102, 116, 121, 112, 100, 97, 115, 104, 0, 0, 0, 0,
-            ]) {
+            if !video_file.starts_with(&DASH_HEADER) {
                 match std::fs::remove_file(&path) {
                     Ok(_) => println!(
                         "[INFO] Removing file {:?} because the video file does not contain the header",
@@ -135,7 +133,7 @@ pub fn write_video(buffer: &mut impl Write, video: &YoutubeMusicVideoRef) {
     write_str(buffer, &video.duration);
 }
 
-fn write_str(cursor: &mut impl Write, value: &str) {
+pub(crate) fn write_str(cursor: &mut impl Write, value: &str) {
     write_u32(cursor, value.len() as u32);
     cursor.write_all(value.as_bytes()).unwrap();
 }
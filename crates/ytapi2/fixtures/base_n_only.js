@@ -0,0 +1,8 @@
+// Player base.js reduced to the n function, called with `String.fromCharCode(110)`.
+// The signature function is missing, as when a new player changes its shape.
+var _yt_player={};(function(g){var window=this;
+'use strict';
+Hta=function(a){var b=a.split("");b.reverse();return b.join("")};
+g.k=Xla.prototype;g.k.vj=function(a){var b=String.fromCharCode(110),c=a.get(b))&&(c=Hta(c),a.set(b,c))};
+g.Ina={sts:20173};
+})(_yt_player);
@@ -0,0 +1,17 @@
+// Player base.js reduced to the code the transforms are extracted from:
+// the signature function with its helper object, and the n function called through an array.
+var _yt_player={};(function(g){var window=this;/*
+
+ Copyright The Closure Library Authors.
+ SPDX-License-Identifier: Apache-2.0
+*/
+'use strict';
+var Nu={VP:function(a){a.reverse()},
+eG:function(a,b){a.splice(0,b)},
+Zq:function(a,b){var c=a[0];a[0]=a[b%a.length];a[b%a.length]=c}};
+Fma=function(a){a=a.split("");Nu.eG(a,3);Nu.Zq(a,7);Nu.VP(a,52);Nu.Zq(a,22);Nu.eG(a,1);return a.join("")};
+var Jra=[Vya];
+Vya=function(a){var b=a.split(""),c=["{",function(d){d.reverse()},"}"];if(typeof Kfa==="undefined")return a;c[1](b);try{b.push(b.shift())}catch(e){return"enhanced_except_"+a}return b.join("")};
+g.k=Xla.prototype;g.k.vj=function(a){a.D&&(b=a.get("n"))&&(b=Jra[0](b),a.set("n",b))};
+g.Ina={signatureTimestamp:19834,useSignatureTimestamp:!0};
+})(_yt_player);
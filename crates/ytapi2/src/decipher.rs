@@ -0,0 +1,418 @@
+use std::path::Path;
+
+use log::{debug, trace, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::types::{Result, YoutubeMusicAudioFormat, YoutubeMusicError};
+
+/// An operation of the signature transform, applied on the characters of the signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherOperation {
+    Reverse,
+    Splice(usize),
+    Swap(usize),
+}
+
+/// Transforms extracted from a player `base.js`.
+/// They only change when YouTube ships a new player, so they are cached by player id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerTransforms {
+    pub player_id: String,
+    /// Must be sent to the player endpoint for the ciphers to match this player
+    pub signature_timestamp: Option<u64>,
+    /// Missing when the signature function can't be found, only the direct urls are usable then
+    pub signature_operations: Option<Vec<CipherOperation>>,
+    /// Source of the javascript function computing the `n` parameter
+    pub n_function: Option<String>,
+}
+
+impl PlayerTransforms {
+    /// Extracts the transforms from the source of a player `base.js`.
+    /// Doesn't do any IO, so saved players can be used to check the parsing.
+    pub fn from_base_js(player_id: &str, js: &str) -> Self {
+        let signature_timestamp = Regex::new(r"(?:signatureTimestamp|sts)\s*:\s*(\d+)")
+            .unwrap()
+            .captures(js)
+            .and_then(|x| x[1].parse().ok());
+        let signature_operations = extract_signature_operations(js)
+            .inspect_err(|e| {
+                warn!("Can't decipher the signatures of player {player_id}: {e:?}");
+            })
+            .ok();
+        let n_function = extract_n_function(js);
+        if n_function.is_none() {
+            warn!("Can't find the n function of player {player_id}, streams may be throttled");
+        }
+        trace!(
+            "Player {player_id}: sts {signature_timestamp:?}, {:?} signature operations",
+            signature_operations.as_ref().map(Vec::len)
+        );
+        Self {
+            player_id: player_id.to_string(),
+            signature_timestamp,
+            signature_operations,
+            n_function,
+        }
+    }
+
+    /// Whether every transform was found, the incomplete ones aren't cached on disk
+    /// so the extraction is tried again on the next player fetch
+    pub fn is_complete(&self) -> bool {
+        self.signature_operations.is_some() && self.n_function.is_some()
+    }
+
+    /// Reads the transforms of the player from `<cache_dir>/<player_id>.json`
+    pub fn load(cache_dir: &Path, player_id: &str) -> Option<Self> {
+        let content = std::fs::read_to_string(cache_dir.join(format!("{player_id}.json"))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Writes the transforms to `<cache_dir>/<player_id>.json`
+    pub fn save(&self, cache_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(cache_dir).map_err(YoutubeMusicError::IoError)?;
+        std::fs::write(
+            cache_dir.join(format!("{}.json", self.player_id)),
+            serde_json::to_string(self).map_err(YoutubeMusicError::SerdeJson)?,
+        )
+        .map_err(YoutubeMusicError::IoError)
+    }
+
+    pub fn decipher_signature(&self, signature: &str) -> Result<String> {
+        let operations = self.signature_operations.as_ref().ok_or_else(|| {
+            YoutubeMusicError::CantDecipher(format!(
+                "No signature function in player {}",
+                self.player_id
+            ))
+        })?;
+        let mut chars = signature.chars().collect::<Vec<_>>();
+        for operation in operations {
+            match *operation {
+                CipherOperation::Reverse => chars.reverse(),
+                CipherOperation::Splice(n) => {
+                    chars.drain(..n.min(chars.len()));
+                }
+                CipherOperation::Swap(n) => {
+                    if !chars.is_empty() {
+                        let n = n % chars.len();
+                        chars.swap(0, n);
+                    }
+                }
+            }
+        }
+        Ok(chars.into_iter().collect())
+    }
+
+    /// Runs the `n` function of the player on the given parameter
+    pub fn transform_n(&self, n: &str) -> Result<String> {
+        let function = self.n_function.as_ref().ok_or_else(|| {
+            YoutubeMusicError::CantDecipher(format!("No n function in player {}", self.player_id))
+        })?;
+        let runtime = rquickjs::Runtime::new()
+            .map_err(|e| YoutubeMusicError::CantDecipher(format!("{e:?}")))?;
+        let context = rquickjs::Context::full(&runtime)
+            .map_err(|e| YoutubeMusicError::CantDecipher(format!("{e:?}")))?;
+        let output = context
+            .with(|ctx| ctx.eval::<String, _>(format!("({function})({})", Value::from(n))))
+            .map_err(|e| YoutubeMusicError::CantDecipher(format!("{e:?}")))?;
+        // The function returns its input prefixed by `enhanced_except_` when it fails
+        if output.starts_with("enhanced_except") || output == n {
+            return Err(YoutubeMusicError::CantDecipher(format!(
+                "The n function of player {} failed on {n}",
+                self.player_id
+            )));
+        }
+        Ok(output)
+    }
+
+    /// Returns the playable url of a format.
+    /// The signature is deciphered when needed and the `n` parameter is transformed.
+    pub fn get_url(&self, format: &YoutubeMusicAudioFormat) -> Result<String> {
+        let mut url = if let Some(url) = &format.url {
+            Url::parse(url)
+        } else {
+            let cipher = format.signature_cipher.as_ref().ok_or_else(|| {
+                YoutubeMusicError::CantDecipher(format!("Format {} has no url", format.itag))
+            })?;
+            let fields = url::form_urlencoded::parse(cipher.as_bytes()).collect::<Vec<_>>();
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.to_string())
+            };
+            let base = field("url").ok_or_else(|| {
+                YoutubeMusicError::CantDecipher(format!("No url in the cipher of {}", format.itag))
+            })?;
+            let signature = field("s").ok_or_else(|| {
+                YoutubeMusicError::CantDecipher(format!("No s in the cipher of {}", format.itag))
+            })?;
+            let signature = self.decipher_signature(&signature)?;
+            let signature_key = field("sp").unwrap_or_else(|| "signature".to_string());
+            Url::parse(&base).map(|mut url| {
+                url.query_pairs_mut()
+                    .append_pair(&signature_key, &signature);
+                url
+            })
+        }
+        .map_err(|e| YoutubeMusicError::CantDecipher(format!("Invalid url: {e}")))?;
+
+        let n = url
+            .query_pairs()
+            .find(|(k, _)| k == "n")
+            .map(|(_, v)| v.to_string());
+        if let Some(n) = n {
+            match self.transform_n(&n) {
+                Ok(transformed) => {
+                    let pairs = url
+                        .query_pairs()
+                        .map(|(k, v)| {
+                            let v = if k == "n" {
+                                transformed.clone()
+                            } else {
+                                v.to_string()
+                            };
+                            (k.to_string(), v)
+                        })
+                        .collect::<Vec<_>>();
+                    url.query_pairs_mut().clear().extend_pairs(pairs);
+                }
+                // The stream still works without it, it's only throttled
+                Err(e) => warn!("Can't transform n of format {}: {e:?}", format.itag),
+            }
+        }
+        Ok(url.to_string())
+    }
+}
+
+/// Returns the id of a player from the url of its `base.js`,
+/// ie `/s/player/<id>/player_ias.vflset/en_US/base.js`
+pub fn get_player_id(player_url: &str) -> Option<&str> {
+    let start = player_url.find("/player/")? + "/player/".len();
+    player_url[start..].split('/').next()
+}
+
+/// Finds the signature function (`a=a.split("");...;return a.join("")`)
+/// and translates the calls to its helper object.
+fn extract_signature_operations(js: &str) -> Result<Vec<CipherOperation>> {
+    let body = Regex::new(
+        r#"function\(\s*([\w$]+)\s*\)\s*\{\s*([\w$]+)\s*=\s*([\w$]+)\.split\(\s*""\s*\)\s*;(.*?)return\s+([\w$]+)\.join\(\s*""\s*\)"#,
+    )
+    .unwrap()
+    .captures_iter(js)
+    .find(|x| x[1] == x[2] && x[1] == x[3] && x[1] == x[5])
+    .map(|x| x[4].to_string())
+    .ok_or_else(|| YoutubeMusicError::CantDecipher("Can't find the signature function".into()))?;
+    debug!("Signature function body: {body}");
+
+    let calls = Regex::new(r#"([\w$]+)(?:\.([\w$]+)|\["([\w$]+)"\])\(\s*[\w$]+\s*,\s*(\d+)\s*\)"#)
+        .unwrap()
+        .captures_iter(&body)
+        .flat_map(|x| {
+            let method = x.get(2).or_else(|| x.get(3))?.as_str().to_string();
+            Some((x[1].to_string(), method, x[4].parse::<usize>().ok()?))
+        })
+        .collect::<Vec<_>>();
+    let helper = calls
+        .first()
+        .map(|(helper, _, _)| helper.clone())
+        .ok_or_else(|| {
+            YoutubeMusicError::CantDecipher("The signature function has no operation".into())
+        })?;
+
+    let helper_body = Regex::new(&format!(
+        r"(?s)var\s+{}\s*=\s*\{{(.*?)\}};",
+        regex::escape(&helper)
+    ))
+    .unwrap()
+    .captures(js)
+    .map(|x| x[1].to_string())
+    .ok_or_else(|| YoutubeMusicError::CantDecipher(format!("Can't find helper {helper}")))?;
+    let methods = Regex::new(r#"(?s)"?([\w$]+)"?\s*:\s*function\s*\([^)]*\)\s*\{(.*?)\}"#)
+        .unwrap()
+        .captures_iter(&helper_body)
+        .map(|x| (x[1].to_string(), x[2].to_string()))
+        .collect::<Vec<_>>();
+
+    calls
+        .into_iter()
+        .map(|(_, method, argument)| {
+            let (_, body) = methods
+                .iter()
+                .find(|(name, _)| *name == method)
+                .ok_or_else(|| {
+                    YoutubeMusicError::CantDecipher(format!("Can't find method {helper}.{method}"))
+                })?;
+            Ok(if body.contains("reverse") {
+                CipherOperation::Reverse
+            } else if body.contains("splice") {
+                CipherOperation::Splice(argument)
+            } else {
+                CipherOperation::Swap(argument)
+            })
+        })
+        .collect()
+}
+
+/// Finds the function transforming the `n` parameter and returns its source
+fn extract_n_function(js: &str) -> Option<String> {
+    let call = Regex::new(
+        r#"(?:\.get\("n"\)\)&&\(b=|b=String\.fromCharCode\(110\),c=a\.get\(b\)\)&&\(c=)([\w$]+)(?:\[(\d+)\])?\([\w$]\)"#,
+    )
+    .unwrap()
+    .captures(js)?;
+    let mut name = call[1].to_string();
+    if let Some(index) = call.get(2) {
+        // The function is stored in an array: `var XX=[name];`
+        let index = index.as_str().parse::<usize>().ok()?;
+        let array = Regex::new(&format!(r"var\s+{}\s*=\s*\[(.+?)\]", regex::escape(&name)))
+            .unwrap()
+            .captures(js)?;
+        name = array[1].split(',').nth(index)?.trim().to_string();
+    }
+    let source = extract_function(js, &name)?;
+    // Global checks fail when the function runs alone, the function then returns its input
+    let source = Regex::new(
+        r#";\s*if\s*\(\s*typeof\s+[\w$]+\s*===?\s*(?:"undefined"|'undefined')\s*\)\s*return\s+[\w$]+;"#,
+    )
+    .unwrap()
+    .replace_all(&source, ";")
+    .to_string();
+    Some(source)
+}
+
+/// Returns the source of `name=function(...){...}` by matching the braces of the body.
+/// String literals are skipped so braces inside them don't count.
+fn extract_function(js: &str, name: &str) -> Option<String> {
+    let start = Regex::new(&format!(
+        r"(?:^|[^\w$.]){}\s*=\s*function\s*\(",
+        regex::escape(name)
+    ))
+    .unwrap()
+    .find(js)?
+    .end();
+    let function_start = js[..start].rfind("function")?;
+    let bytes = js.as_bytes();
+    let mut index = js[start..].find('{')? + start;
+    let mut depth = 0;
+    let mut quote = None;
+    while index < bytes.len() {
+        let c = bytes[index];
+        match quote {
+            Some(_) if c == b'\\' => index += 1,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if matches!(c, b'"' | b'\'' | b'`') => quote = Some(c),
+            None if c == b'{' => depth += 1,
+            None if c == b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(js[function_start..=index].to_string());
+                }
+            }
+            None => (),
+        }
+        index += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIG_AND_N: &str = include_str!("../fixtures/base_sig_and_n.js");
+    const N_ONLY: &str = include_str!("../fixtures/base_n_only.js");
+
+    fn format(url: Option<&str>, signature_cipher: Option<&str>) -> YoutubeMusicAudioFormat {
+        YoutubeMusicAudioFormat {
+            itag: 251,
+            mime_type: "audio/webm; codecs=\"opus\"".to_string(),
+            bitrate: 160000,
+            content_length: None,
+            loudness_db: None,
+            url: url.map(str::to_string),
+            signature_cipher: signature_cipher.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn extracts_transforms() {
+        let transforms = PlayerTransforms::from_base_js("sig_and_n", SIG_AND_N);
+        assert_eq!(transforms.signature_timestamp, Some(19834));
+        assert_eq!(
+            transforms.signature_operations,
+            Some(vec![
+                CipherOperation::Splice(3),
+                CipherOperation::Swap(7),
+                CipherOperation::Reverse,
+                CipherOperation::Swap(22),
+                CipherOperation::Splice(1),
+            ])
+        );
+        assert!(transforms.is_complete());
+    }
+
+    #[test]
+    fn deciphers_signature() {
+        let transforms = PlayerTransforms::from_base_js("sig_and_n", SIG_AND_N);
+        assert_eq!(
+            transforms
+                .decipher_signature(
+                    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
+                )
+                .unwrap(),
+            "876543210zyxwvutsrqpo9mlkjihgfedcbaZYXWVUTSRQPONMLDJIHGFEK"
+        );
+    }
+
+    #[test]
+    fn transforms_n() {
+        let transforms = PlayerTransforms::from_base_js("sig_and_n", SIG_AND_N);
+        // The `typeof` check is removed, otherwise the input would come back unchanged
+        assert_eq!(transforms.transform_n("abcdef").unwrap(), "edcbaf");
+
+        let transforms = PlayerTransforms::from_base_js("n_only", N_ONLY);
+        assert_eq!(transforms.signature_timestamp, Some(20173));
+        assert_eq!(transforms.transform_n("abcdef").unwrap(), "fedcba");
+    }
+
+    #[test]
+    fn builds_ciphered_url() {
+        let transforms = PlayerTransforms::from_base_js("sig_and_n", SIG_AND_N);
+        let format = format(
+            None,
+            Some(
+                "s=ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789&sp=sig&url=https%3A%2F%2Frr1---sn-example.googlevideo.com%2Fvideoplayback%3Fitag%3D251%26n%3Dabcdef",
+            ),
+        );
+        assert_eq!(
+            transforms.get_url(&format).unwrap(),
+            "https://rr1---sn-example.googlevideo.com/videoplayback?itag=251&n=edcbaf&sig=876543210zyxwvutsrqpo9mlkjihgfedcbaZYXWVUTSRQPONMLDJIHGFEK"
+        );
+    }
+
+    #[test]
+    fn keeps_direct_urls_without_signature_function() {
+        let transforms = PlayerTransforms::from_base_js("n_only", N_ONLY);
+        assert_eq!(transforms.signature_operations, None);
+        assert!(!transforms.is_complete());
+        assert!(transforms.decipher_signature("ABCDEF").is_err());
+
+        let direct = format(
+            Some("https://rr1---sn-example.googlevideo.com/videoplayback?itag=251&n=abcdef"),
+            None,
+        );
+        assert_eq!(
+            transforms.get_url(&direct).unwrap(),
+            "https://rr1---sn-example.googlevideo.com/videoplayback?itag=251&n=fedcba"
+        );
+        let ciphered = format(
+            None,
+            Some("s=ABCDEF&url=https%3A%2F%2Frr1---sn-example.googlevideo.com%2Fvideoplayback"),
+        );
+        assert!(transforms.get_url(&ciphered).is_err());
+    }
+}
@@ -1,10 +1,49 @@
+use serde_json::Value;
+
+/// Name and version of the Android app client, the only one given the timed lyrics
+const ANDROID_MUSIC_CLIENT: (&str, &str) = ("ANDROID_MUSIC", "7.21.50");
+
 #[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash)]
 pub enum Endpoint {
     MusicLikedPlaylists,
     MusicHome,
     MusicLibraryLanding,
     Playlist(String),
+    Album(String),
+    Artist(String),
+    /// Browse id and params of the full content of an artist shelf
+    ArtistShelf(String, Option<String>),
     Search(String),
+    FilteredSearch(String, SearchFilter),
+    SearchSuggestions(String),
+    /// The automix queue of a video id
+    WatchNext(String),
+    Lyrics(String),
+    /// Video id and signature timestamp of the player used to decipher the streams
+    Player(String, Option<u64>),
+}
+
+/// The category a search is restricted to.
+#[derive(Debug, Clone, Copy, PartialOrd, Eq, Ord, PartialEq, Hash)]
+pub enum SearchFilter {
+    Songs,
+    Videos,
+    Albums,
+    Artists,
+    CommunityPlaylists,
+}
+
+impl SearchFilter {
+    /// The `params` sent by the web client when the filter chip is selected
+    pub fn get_params(&self) -> &'static str {
+        match self {
+            SearchFilter::Songs => "EgWKAQIIAWoMEA4QChADEAQQCRAF",
+            SearchFilter::Videos => "EgWKAQIQAWoMEA4QChADEAQQCRAF",
+            SearchFilter::Albums => "EgWKAQIYAWoMEA4QChADEAQQCRAF",
+            SearchFilter::Artists => "EgWKAQIgAWoMEA4QChADEAQQCRAF",
+            SearchFilter::CommunityPlaylists => "EgeKAQQoAEABagwQDhAKEAMQBBAJEAU%3D",
+        }
+    }
 }
 
 impl Endpoint {
@@ -13,8 +52,16 @@ impl Endpoint {
             Endpoint::MusicLikedPlaylists => "browseId".to_owned(),
             Endpoint::MusicLibraryLanding => "browseId".to_owned(),
             Endpoint::Playlist(_) => "browseId".to_owned(),
+            Endpoint::Album(_) => "browseId".to_owned(),
+            Endpoint::Artist(_) => "browseId".to_owned(),
+            Endpoint::ArtistShelf(_, _) => "browseId".to_owned(),
             Endpoint::MusicHome => "browseId".to_owned(),
             Endpoint::Search(_) => "query".to_owned(),
+            Endpoint::FilteredSearch(_, _) => "query".to_owned(),
+            Endpoint::SearchSuggestions(_) => "input".to_owned(),
+            Endpoint::WatchNext(_) => "videoId".to_owned(),
+            Endpoint::Lyrics(_) => "browseId".to_owned(),
+            Endpoint::Player(_, _) => "videoId".to_owned(),
         }
     }
     pub fn get_param(&self) -> String {
@@ -22,16 +69,66 @@ impl Endpoint {
             Endpoint::MusicLikedPlaylists => "FEmusic_liked_playlists".to_owned(),
             Endpoint::MusicLibraryLanding => "FEmusic_library_landing".to_owned(),
             Endpoint::Playlist(id) => id.to_owned(),
+            Endpoint::Album(id) => id.to_owned(),
+            Endpoint::Artist(id) => id.to_owned(),
+            Endpoint::ArtistShelf(id, _) => id.to_owned(),
             Endpoint::Search(query) => query.to_owned(),
+            Endpoint::FilteredSearch(query, _) => query.to_owned(),
+            Endpoint::SearchSuggestions(prefix) => prefix.to_owned(),
+            Endpoint::WatchNext(id) => id.to_owned(),
+            Endpoint::Lyrics(id) => id.to_owned(),
+            Endpoint::Player(id, _) => id.to_owned(),
             Endpoint::MusicHome => "FEmusic_home".to_owned(),
         }
     }
+    /// Fields added to the request body next to the key and param
+    pub fn get_extra_fields(&self) -> Vec<(&'static str, Value)> {
+        match self {
+            Endpoint::FilteredSearch(_, filter) => vec![("params", filter.get_params().into())],
+            Endpoint::ArtistShelf(_, Some(params)) => vec![("params", params.as_str().into())],
+            Endpoint::WatchNext(id) => vec![
+                ("playlistId", format!("RDAMVM{id}").into()),
+                ("isAudioOnly", true.into()),
+                ("enablePersistentPlaylistPanel", true.into()),
+                ("tunerSettingValue", "AUTOMIX_SETTING_NORMAL".into()),
+            ],
+            Endpoint::Player(_, signature_timestamp) => {
+                let mut fields = vec![
+                    ("racyCheckOk", true.into()),
+                    ("contentCheckOk", true.into()),
+                ];
+                if let Some(sts) = signature_timestamp {
+                    fields.push((
+                        "playbackContext",
+                        serde_json::json!({"contentPlaybackContext": {"signatureTimestamp": sts}}),
+                    ));
+                }
+                fields
+            }
+            _ => Vec::new(),
+        }
+    }
+    /// Name and version of the client sent in the context instead of the web client
+    pub fn get_client(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Endpoint::Lyrics(_) => Some(ANDROID_MUSIC_CLIENT),
+            _ => None,
+        }
+    }
     pub fn get_route(&self) -> String {
         match self {
             Endpoint::MusicLikedPlaylists => "browse".to_owned(),
             Endpoint::MusicLibraryLanding => "browse".to_owned(),
             Endpoint::Playlist(_) => "browse".to_owned(),
+            Endpoint::Album(_) => "browse".to_owned(),
+            Endpoint::Artist(_) => "browse".to_owned(),
+            Endpoint::ArtistShelf(_, _) => "browse".to_owned(),
             Endpoint::Search(_) => "search".to_owned(),
+            Endpoint::FilteredSearch(_, _) => "search".to_owned(),
+            Endpoint::SearchSuggestions(_) => "music/get_search_suggestions".to_owned(),
+            Endpoint::WatchNext(_) => "next".to_owned(),
+            Endpoint::Lyrics(_) => "browse".to_owned(),
+            Endpoint::Player(_, _) => "player".to_owned(),
             Endpoint::MusicHome => "browse".to_owned(),
         }
     }
@@ -1,20 +1,29 @@
 use std::{
+    collections::HashSet,
     path::Path,
+    sync::Mutex,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use log::{debug, error, trace};
+use log::{debug, error, trace, warn};
 use reqwest::header::HeaderMap;
 use serde_json::Value;
 use sha1::{Digest, Sha1};
 
 use crate::{
-    endpoint::Endpoint,
+    decipher::{PlayerTransforms, get_player_id},
+    endpoint::{Endpoint, SearchFilter},
     json_extractor::{
-        Continuation, extract_playlist_info, from_json, get_continuation, get_playlist, get_video,
-        get_video_from_album,
+        Continuation, extract_playlist_info, from_json, get_continuation, get_list_item_video,
+        get_lyrics_browse_id, get_panel_video, get_playability_status, get_playlist,
+        get_playlist_item, get_two_row_item_playlist, parse_album, parse_artist, parse_lyrics,
+        parse_player, parse_playlist_header, parse_search_results, parse_search_suggestions,
+    },
+    types::{
+        ArtistShelfRef, Result, SearchResults, SearchSuggestions, YoutubeMusicAlbum,
+        YoutubeMusicArtist, YoutubeMusicError, YoutubeMusicLyrics, YoutubeMusicPlayer,
+        YoutubeMusicPlaylist, YoutubeMusicPlaylistRef, YoutubeMusicVideoRef,
     },
-    types::{Result, YoutubeMusicError, YoutubeMusicPlaylistRef, YoutubeMusicVideoRef},
     utils::StringUtils,
 };
 
@@ -27,6 +36,9 @@ pub struct YoutubeMusicInstance {
     client_version: String,
     cookies: String,
     account_id: Option<String>,
+    player_url: Option<String>,
+    /// Transforms of the player, kept for the session once complete
+    player_transforms: Mutex<Option<PlayerTransforms>>,
 }
 
 impl YoutubeMusicInstance {
@@ -84,6 +96,11 @@ impl YoutubeMusicInstance {
             })?;
 
         trace!("Innertube client version: {}", client_version);
+
+        let player_url = response
+            .between("\"jsUrl\":\"", "\"")
+            .map(|x| x.to_string());
+        trace!("Player url: {player_url:?}");
         trace!("account id {:?}", account_id);
 
         Ok(Self {
@@ -92,6 +109,8 @@ impl YoutubeMusicInstance {
             client_version: client_version.to_string(),
             cookies,
             account_id,
+            player_url,
+            player_transforms: Mutex::new(None),
         })
     }
 
@@ -157,22 +176,17 @@ impl YoutubeMusicInstance {
         endpoint_route: &str,
         endpoint_key: &str,
         endpoint_param: &str,
+        extra_fields: &[(&str, Value)],
+        client: Option<(&str, &str)>,
     ) -> Result<String> {
         trace!("Browse {endpoint_route}");
         let url = format!(
             "https://music.youtube.com/youtubei/v1/{endpoint_route}?key={}&prettyPrint=false",
             self.innertube_api_key
         );
-        let body = match &self.account_id {
-            Some(id) => format!(
-                r#"{{"context":{{"client":{{"clientName":"WEB_REMIX","clientVersion":"{}"}},"user":{{"onBehalfOfUser":"{id}"}}}},"{endpoint_key}":"{endpoint_param}"}}"#,
-                self.client_version,
-            ),
-            None => format!(
-                r#"{{"context":{{"client":{{"clientName":"WEB_REMIX","clientVersion":"{}"}}}},"{endpoint_key}":"{endpoint_param}"}}"#,
-                self.client_version
-            ),
-        };
+        let mut fields = vec![(endpoint_key, Value::from(endpoint_param))];
+        fields.extend_from_slice(extra_fields);
+        let body = self.request_body(client, &fields);
 
         reqwest::Client::new()
             .post(&url)
@@ -192,6 +206,27 @@ impl YoutubeMusicInstance {
             .map_err(YoutubeMusicError::RequestError)
     }
 
+    /// Builds the json body of a request: the client context followed by the given fields.
+    /// Values are written as json so quotes in search queries can't break the body.
+    /// The web client is used unless another `client` name and version is given.
+    fn request_body(&self, client: Option<(&str, &str)>, fields: &[(&str, Value)]) -> String {
+        let (client_name, client_version) =
+            client.unwrap_or(("WEB_REMIX", self.client_version.as_str()));
+        let mut body = match &self.account_id {
+            Some(id) => format!(
+                r#"{{"context":{{"client":{{"clientName":"{client_name}","clientVersion":"{client_version}"}},"user":{{"onBehalfOfUser":"{id}"}}}}"#,
+            ),
+            None => format!(
+                r#"{{"context":{{"client":{{"clientName":"{client_name}","clientVersion":"{client_version}"}}}}"#,
+            ),
+        };
+        for (key, value) in fields {
+            body.push_str(&format!(r#","{key}":{value}"#));
+        }
+        body.push('}');
+        body
+    }
+
     pub async fn browse(
         &self,
         endpoint: &Endpoint,
@@ -203,6 +238,8 @@ impl YoutubeMusicInstance {
                     &endpoint.get_route(),
                     &endpoint.get_key(),
                     &endpoint.get_param(),
+                    &endpoint.get_extra_fields(),
+                    endpoint.get_client(),
                 )
                 .await?,
         )
@@ -224,30 +261,27 @@ impl YoutubeMusicInstance {
         Ok((playlist_json, continuation))
     }
 
-    pub async fn browse_continuation_raw(
+    pub async fn browse_continuation_raw(&self, continuation: &Continuation) -> Result<String> {
+        self.continuation_raw("browse", continuation, &[]).await
+    }
+
+    async fn continuation_raw(
         &self,
+        endpoint_route: &str,
         Continuation {
             continuation,
             click_tracking_params,
         }: &Continuation,
+        extra_fields: &[(&str, Value)],
     ) -> Result<String> {
-        trace!("Browse continuation {continuation}");
+        trace!("{endpoint_route} continuation {continuation}");
 
         let url = format!(
-            "https://music.youtube.com/youtubei/v1/browse?ctoken={continuation}&continuation={continuation}&type=next&itct={click_tracking_params}&key={}&prettyPrint=false",
+            "https://music.youtube.com/youtubei/v1/{endpoint_route}?ctoken={continuation}&continuation={continuation}&type=next&itct={click_tracking_params}&key={}&prettyPrint=false",
             self.innertube_api_key
         );
 
-        let body = match &self.account_id {
-            Some(id) => format!(
-                r#"{{"context":{{"client":{{"clientName":"WEB_REMIX","clientVersion":"{}"}},"user":{{"onBehalfOfUser":"{id}"}}}}}}"#,
-                self.client_version
-            ),
-            None => format!(
-                r#"{{"context":{{"client":{{"clientName":"WEB_REMIX","clientVersion":"{}"}}}}}}"#,
-                self.client_version
-            ),
-        };
+        let body = self.request_body(None, extra_fields);
 
         reqwest::Client::new()
             .post(&url)
@@ -329,6 +363,333 @@ impl YoutubeMusicInstance {
         Ok(library)
     }
 
+    pub async fn search(&self, query: &str) -> Result<SearchResults> {
+        self.search_endpoint(&Endpoint::Search(query.to_owned()))
+            .await
+    }
+
+    pub async fn search_filtered(
+        &self,
+        query: &str,
+        filter: SearchFilter,
+    ) -> Result<SearchResults> {
+        self.search_endpoint(&Endpoint::FilteredSearch(query.to_owned(), filter))
+            .await
+    }
+
+    async fn search_endpoint(&self, endpoint: &Endpoint) -> Result<SearchResults> {
+        let (search_json, continuations) = self.browse(endpoint, true).await?;
+
+        trace!("Fetched search results");
+        debug!("Continuations: {continuations:?}");
+
+        parse_search_results(&search_json, continuations)
+    }
+
+    pub async fn search_continuation(&self, continuation: &Continuation) -> Result<SearchResults> {
+        let search_json: Value =
+            serde_json::from_str(&self.continuation_raw("search", continuation, &[]).await?)
+                .map_err(YoutubeMusicError::SerdeJson)?;
+
+        debug!("Search continuation response: {search_json}");
+        if search_json.get("error").is_some() {
+            error!("Error in search_continuation");
+            error!("{:?}", search_json);
+            return Err(YoutubeMusicError::YoutubeMusicError(search_json));
+        }
+        let continuations = from_json(&search_json, get_continuation)?;
+        parse_search_results(&search_json, continuations)
+    }
+
+    /// Fetches the completions the web client shows while the query is typed
+    pub async fn get_search_suggestions(&self, prefix: &str) -> Result<SearchSuggestions> {
+        let endpoint = Endpoint::SearchSuggestions(prefix.to_owned());
+        let (suggestions_json, _) = self.browse(&endpoint, false).await?;
+
+        trace!("Fetched search suggestions");
+        parse_search_suggestions(&suggestions_json)
+    }
+
+    pub async fn get_album(&self, browse_id: &str) -> Result<YoutubeMusicAlbum> {
+        let endpoint = Endpoint::Album(browse_id.to_owned());
+        let (album_json, _) = self.browse(&endpoint, false).await?;
+
+        trace!("Fetched album {browse_id}");
+        parse_album(&album_json, browse_id).ok_or_else(|| {
+            error!("Can't parse album {browse_id}");
+            YoutubeMusicError::Other(format!("Can't find the header of album {browse_id}"))
+        })
+    }
+
+    pub async fn get_artist(&self, channel_id: &str) -> Result<YoutubeMusicArtist> {
+        let endpoint = Endpoint::Artist(channel_id.to_owned());
+        let (artist_json, _) = self.browse(&endpoint, false).await?;
+
+        trace!("Fetched artist {channel_id}");
+        parse_artist(&artist_json, channel_id).ok_or_else(|| {
+            error!("Can't parse artist {channel_id}");
+            YoutubeMusicError::Other(format!("Can't find the header of artist {channel_id}"))
+        })
+    }
+
+    /// Fetches the full content of the songs or videos shelf of an artist.
+    /// At most `max_continuations` continuation requests are made to fetch the remaining tracks.
+    pub async fn get_artist_shelf_tracks(
+        &self,
+        shelf: &ArtistShelfRef,
+        max_continuations: usize,
+    ) -> Result<Vec<YoutubeMusicVideoRef>> {
+        self.browse_artist_shelf(shelf, max_continuations, get_list_item_video)
+            .await
+    }
+
+    /// Fetches the full content of the albums or singles shelf of an artist.
+    /// At most `max_continuations` continuation requests are made to fetch the remaining releases.
+    pub async fn get_artist_shelf_releases(
+        &self,
+        shelf: &ArtistShelfRef,
+        max_continuations: usize,
+    ) -> Result<Vec<YoutubeMusicPlaylistRef>> {
+        self.browse_artist_shelf(shelf, max_continuations, get_two_row_item_playlist)
+            .await
+    }
+
+    async fn browse_artist_shelf<T: PartialEq>(
+        &self,
+        shelf: &ArtistShelfRef,
+        max_continuations: usize,
+        transformer: impl Fn(&Value) -> Option<T>,
+    ) -> Result<Vec<T>> {
+        let endpoint = Endpoint::ArtistShelf(shelf.browse_id.clone(), shelf.params.clone());
+        let (shelf_json, mut continuations) = self.browse(&endpoint, max_continuations > 0).await?;
+        let mut items = from_json(&shelf_json, &transformer)?;
+
+        let mut fetched = 0;
+        while let Some(continuation) = continuations.pop() {
+            if fetched == max_continuations {
+                break;
+            }
+            fetched += 1;
+            trace!(
+                "Fetching continuation {fetched} of artist shelf {}",
+                shelf.browse_id
+            );
+
+            let (continuation_json, new_continuations) =
+                self.browse_continuation(&continuation, true).await?;
+            continuations.extend(new_continuations);
+            items.extend(from_json(&continuation_json, &transformer)?);
+        }
+        Ok(items)
+    }
+
+    /// Fetches a playlist and its tracks.
+    /// At most `max_continuations` continuation requests are made to fetch the remaining tracks.
+    pub async fn get_playlist(
+        &self,
+        browse_id: &str,
+        max_continuations: usize,
+    ) -> Result<YoutubeMusicPlaylist> {
+        let browse_id = if browse_id.starts_with("VL") {
+            browse_id.to_owned()
+        } else {
+            format!("VL{browse_id}")
+        };
+        let endpoint = Endpoint::Playlist(browse_id.clone());
+        let (playlist_json, mut continuations) =
+            self.browse(&endpoint, max_continuations > 0).await?;
+
+        trace!("Fetched playlist {browse_id}");
+        let mut playlist = parse_playlist_header(&playlist_json, &browse_id).ok_or_else(|| {
+            error!("Can't parse playlist {browse_id}");
+            YoutubeMusicError::Other(format!("Can't find the header of playlist {browse_id}"))
+        })?;
+        // Entries are only deduplicated on their set id, so a video added twice
+        // to the playlist is kept twice
+        let mut seen = HashSet::new();
+        let mut push_tracks = |tracks: Vec<(Option<String>, YoutubeMusicVideoRef)>| {
+            for (set_video_id, video) in tracks {
+                if set_video_id.is_none_or(|x| seen.insert(x)) {
+                    playlist.tracks.push(video);
+                }
+            }
+        };
+        push_tracks(parse_playlist(&playlist_json)?);
+
+        let mut fetched = 0;
+        while let Some(continuation) = continuations.pop() {
+            if fetched == max_continuations {
+                break;
+            }
+            fetched += 1;
+            trace!("Fetching continuation {fetched} of playlist {browse_id}");
+
+            let (continuation_json, new_continuations) =
+                self.browse_continuation(&continuation, true).await?;
+            continuations.extend(new_continuations);
+
+            push_tracks(parse_playlist(&continuation_json)?);
+        }
+        trace!(
+            "Fetched {} tracks of playlist {browse_id}",
+            playlist.tracks.len()
+        );
+
+        Ok(playlist)
+    }
+
+    /// Fetches the automix queue the web client plays after the given song.
+    /// The seed song is the first track of the queue.
+    pub async fn get_radio(
+        &self,
+        video_id: &str,
+    ) -> Result<(Vec<YoutubeMusicVideoRef>, Vec<Continuation>)> {
+        let endpoint = Endpoint::WatchNext(video_id.to_owned());
+        let (radio_json, continuations) = self.browse(&endpoint, true).await?;
+
+        trace!("Fetched radio of {video_id}");
+        Ok((from_json(&radio_json, get_panel_video)?, continuations))
+    }
+
+    /// Extends the automix queue of the given song.
+    /// The returned continuations can be followed again to make the radio endless.
+    pub async fn get_radio_continuation(
+        &self,
+        video_id: &str,
+        continuation: &Continuation,
+    ) -> Result<(Vec<YoutubeMusicVideoRef>, Vec<Continuation>)> {
+        let endpoint = Endpoint::WatchNext(video_id.to_owned());
+        let key = endpoint.get_key();
+        let mut fields = vec![(key.as_str(), Value::from(endpoint.get_param()))];
+        fields.extend(endpoint.get_extra_fields());
+
+        let radio_json: Value = serde_json::from_str(
+            &self
+                .continuation_raw(&endpoint.get_route(), continuation, &fields)
+                .await?,
+        )
+        .map_err(YoutubeMusicError::SerdeJson)?;
+
+        debug!("Radio continuation response: {radio_json}");
+        if radio_json.get("error").is_some() {
+            error!("Error in get_radio_continuation");
+            error!("{:?}", radio_json);
+            return Err(YoutubeMusicError::YoutubeMusicError(radio_json));
+        }
+        Ok((
+            from_json(&radio_json, get_panel_video)?,
+            from_json(&radio_json, get_continuation)?,
+        ))
+    }
+
+    /// Fetches the lyrics of a song.
+    /// The lyrics page is requested as the Android app, which also gets the timed lines.
+    /// Returns `None` when YouTube Music doesn't have lyrics for it.
+    pub async fn get_lyrics(&self, video_id: &str) -> Result<Option<YoutubeMusicLyrics>> {
+        let (next_json, _) = self
+            .browse(&Endpoint::WatchNext(video_id.to_owned()), false)
+            .await?;
+        let Some(browse_id) = get_lyrics_browse_id(&next_json) else {
+            trace!("No lyrics tab for {video_id}");
+            return Ok(None);
+        };
+
+        let (lyrics_json, _) = self.browse(&Endpoint::Lyrics(browse_id), false).await?;
+        trace!("Fetched lyrics of {video_id}");
+        Ok(parse_lyrics(&lyrics_json))
+    }
+
+    /// Fetches the audio streams of a video.
+    /// Fails with [`YoutubeMusicError::NotPlayable`] when the video is restricted.
+    pub async fn get_player(&self, video_id: &str) -> Result<YoutubeMusicPlayer> {
+        self.get_player_with_timestamp(video_id, None).await
+    }
+
+    /// Fetches the audio streams of a video with playable urls.
+    /// The transforms of the current player are read from `cache_dir` or extracted
+    /// from its `base.js` and cached there.
+    pub async fn get_player_deciphered(
+        &self,
+        video_id: &str,
+        cache_dir: &Path,
+    ) -> Result<YoutubeMusicPlayer> {
+        let transforms = self.get_player_transforms(cache_dir).await?;
+        let mut player = self
+            .get_player_with_timestamp(video_id, transforms.signature_timestamp)
+            .await?;
+        for format in player.formats.iter_mut() {
+            match transforms.get_url(format) {
+                Ok(url) => format.url = Some(url),
+                Err(e) => {
+                    error!("Can't decipher format {} of {video_id}: {e:?}", format.itag);
+                    format.url = None;
+                }
+            }
+        }
+        player.formats.retain(|x| x.url.is_some());
+        Ok(player)
+    }
+
+    pub async fn get_player_transforms(&self, cache_dir: &Path) -> Result<PlayerTransforms> {
+        let player_url = self
+            .player_url
+            .as_ref()
+            .ok_or(YoutubeMusicError::CantFindPlayerUrl)?;
+        let player_id = get_player_id(player_url).ok_or(YoutubeMusicError::CantFindPlayerUrl)?;
+        if let Some(transforms) = self.player_transforms.lock().unwrap().clone() {
+            return Ok(transforms);
+        }
+        if let Some(transforms) =
+            PlayerTransforms::load(cache_dir, player_id).filter(PlayerTransforms::is_complete)
+        {
+            trace!("Loaded transforms of player {player_id} from cache");
+            *self.player_transforms.lock().unwrap() = Some(transforms.clone());
+            return Ok(transforms);
+        }
+
+        trace!("Fetching player {player_url}");
+        let js = reqwest::Client::new()
+            .get(format!("{YT_DOMAIN}{player_url}"))
+            .send()
+            .await
+            .map_err(YoutubeMusicError::RequestError)?
+            .text()
+            .await
+            .map_err(YoutubeMusicError::RequestError)?;
+        let transforms = PlayerTransforms::from_base_js(player_id, &js);
+        // Incomplete transforms are fetched again for the next video
+        if !transforms.is_complete() {
+            warn!("Transforms of player {player_id} are incomplete, they are not cached");
+            return Ok(transforms);
+        }
+        if let Err(e) = transforms.save(cache_dir) {
+            error!("Can't cache transforms of player {player_id}: {e:?}");
+        }
+        *self.player_transforms.lock().unwrap() = Some(transforms.clone());
+        Ok(transforms)
+    }
+
+    async fn get_player_with_timestamp(
+        &self,
+        video_id: &str,
+        signature_timestamp: Option<u64>,
+    ) -> Result<YoutubeMusicPlayer> {
+        let endpoint = Endpoint::Player(video_id.to_owned(), signature_timestamp);
+        let (player_json, _) = self.browse(&endpoint, false).await?;
+
+        if let Some(status) = get_playability_status(&player_json) {
+            error!("Can't play {video_id}: {status}");
+            return Err(YoutubeMusicError::NotPlayable(status));
+        }
+
+        let player = parse_player(&player_json, video_id);
+        trace!(
+            "Fetched {} audio formats of {video_id}",
+            player.formats.len()
+        );
+        Ok(player)
+    }
+
     fn compute_sapi_hash(&self) -> String {
         let start = SystemTime::now();
         let since_the_epoch = start
@@ -350,15 +711,13 @@ impl YoutubeMusicInstance {
     }
 }
 
-fn parse_playlist(playlist_json: &Value) -> Result<Vec<YoutubeMusicVideoRef>> {
-    let mut videos = from_json(playlist_json, get_video)?;
+/// Parses the tracks of a playlist page with their `playlistSetVideoId`
+fn parse_playlist(playlist_json: &Value) -> Result<Vec<(Option<String>, YoutubeMusicVideoRef)>> {
+    let mut videos = from_json(playlist_json, get_playlist_item)?;
     let info = extract_playlist_info(playlist_json);
 
-    for mut video in from_json(playlist_json, get_video_from_album)? {
-        if videos.iter().any(|x| x.video_id == video.video_id) {
-            continue;
-        }
-        if let Some((title, artists)) = info.as_ref() {
+    if let Some((title, artists)) = info.as_ref() {
+        for (_, video) in videos.iter_mut() {
             if video.album.is_empty() {
                 video.album = title.to_string();
             }
@@ -366,7 +725,6 @@ fn parse_playlist(playlist_json: &Value) -> Result<Vec<YoutubeMusicVideoRef>> {
                 video.author = artists.to_string();
             }
         }
-        videos.push(video);
     }
     Ok(videos)
 }
@@ -1,7 +1,13 @@
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::types::{YoutubeMusicPlaylistRef, YoutubeMusicVideoRef};
+pub use crate::types::Continuation;
+use crate::types::{
+    ArtistShelf, ArtistShelfRef, PlayabilityStatus, PlaylistPrivacy, SearchResults,
+    SearchSuggestions, TimedLyricsLine, YoutubeMusicAlbum, YoutubeMusicAlbumTrack,
+    YoutubeMusicArtist, YoutubeMusicArtistRef, YoutubeMusicAudioFormat, YoutubeMusicLyrics,
+    YoutubeMusicPlayer, YoutubeMusicPlaylist, YoutubeMusicPlaylistRef, YoutubeMusicThumbnail,
+    YoutubeMusicVideoRef,
+};
 
 /// Applies recursively the `transformer` function to the given json value
 /// and returns the transformed values.
@@ -58,21 +64,30 @@ pub(crate) fn get_playlist(value: &Value) -> Option<YoutubeMusicPlaylistRef> {
     })
 }
 
-#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
-pub struct Continuation {
-    pub(crate) continuation: String,
-    pub(crate) click_tracking_params: String,
-}
-
 pub fn get_continuation(value: &Value) -> Option<Continuation> {
-    let continuation = value
-        .get("nextContinuationData")
-        .and_then(|x| x.get("continuation"))
-        .and_then(Value::as_str)?;
-    let click_tracking_params = value
+    if let Some(endpoint) = value
+        .get("continuationItemRenderer")
+        .and_then(|x| x.get("continuationEndpoint"))
+    {
+        // Newer responses put a continuation item at the end of the list
+        return Some(Continuation {
+            continuation: endpoint
+                .get("continuationCommand")?
+                .get("token")?
+                .as_str()?
+                .to_string(),
+            click_tracking_params: endpoint
+                .get("clickTrackingParams")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    let data = value
         .get("nextContinuationData")
-        .and_then(|x| x.get("clickTrackingParams"))
-        .and_then(Value::as_str)?;
+        .or_else(|| value.get("nextRadioContinuationData"))?;
+    let continuation = data.get("continuation").and_then(Value::as_str)?;
+    let click_tracking_params = data.get("clickTrackingParams").and_then(Value::as_str)?;
     Some(Continuation {
         continuation: continuation.to_string(),
         click_tracking_params: click_tracking_params.to_string(),
@@ -102,6 +117,739 @@ pub fn get_playlist_search(value: &Value) -> Option<YoutubeMusicPlaylistRef> {
     })
 }
 
+/// A single entry of a search result shelf.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SearchItem {
+    Song(YoutubeMusicVideoRef),
+    Video(YoutubeMusicVideoRef),
+    Album(YoutubeMusicPlaylistRef),
+    Artist(YoutubeMusicPlaylistRef),
+    Playlist(YoutubeMusicPlaylistRef),
+}
+
+/// Tries to extract a search result from a json value.
+/// The kind of the result is deduced from the music video type for tracks
+/// and from the page type of the browse endpoint for everything else.
+pub(crate) fn get_search_item(value: &Value) -> Option<SearchItem> {
+    let renderer = value.get("musicResponsiveListItemRenderer")?;
+    if let Some(video) = get_video_from_item(renderer) {
+        return match find_key(renderer, "musicVideoType").and_then(Value::as_str) {
+            Some("MUSIC_VIDEO_TYPE_ATV") => Some(SearchItem::Song(video)),
+            _ => Some(SearchItem::Video(video)),
+        };
+    }
+    let endpoint = renderer.get("navigationEndpoint")?.get("browseEndpoint")?;
+    let playlist = get_playlist_search(renderer)?;
+    let page_type = find_key(endpoint, "pageType").and_then(Value::as_str);
+    match page_type {
+        Some("MUSIC_PAGE_TYPE_ALBUM") => Some(SearchItem::Album(playlist)),
+        Some("MUSIC_PAGE_TYPE_ARTIST") => Some(SearchItem::Artist(playlist)),
+        Some("MUSIC_PAGE_TYPE_PLAYLIST") => Some(SearchItem::Playlist(playlist)),
+        Some(_) => None,
+        None if playlist.browse_id.starts_with("MPRE") => Some(SearchItem::Album(playlist)),
+        None if playlist.browse_id.starts_with("UC") => Some(SearchItem::Artist(playlist)),
+        None if playlist.browse_id.starts_with("VL") => Some(SearchItem::Playlist(playlist)),
+        None => None,
+    }
+}
+
+/// Sorts every search item found in the json into the typed lists of [`SearchResults`].
+pub(crate) fn parse_search_results(
+    json: &Value,
+    continuations: Vec<Continuation>,
+) -> crate::types::Result<SearchResults> {
+    let mut results = SearchResults {
+        songs: Vec::new(),
+        videos: Vec::new(),
+        albums: Vec::new(),
+        artists: Vec::new(),
+        playlists: Vec::new(),
+        continuations,
+    };
+    for item in from_json(json, get_search_item)? {
+        match item {
+            SearchItem::Song(e) => results.songs.push(e),
+            SearchItem::Video(e) => results.videos.push(e),
+            SearchItem::Album(e) => results.albums.push(e),
+            SearchItem::Artist(e) => results.artists.push(e),
+            SearchItem::Playlist(e) => results.playlists.push(e),
+        }
+    }
+    Ok(results)
+}
+
+/// Tries to extract the query of a search suggestion from a json value.
+pub(crate) fn get_search_suggestion(value: &Value) -> Option<String> {
+    let renderer = value.get("searchSuggestionRenderer")?;
+    renderer
+        .get("navigationEndpoint")
+        .and_then(|x| x.get("searchEndpoint"))
+        .and_then(|x| x.get("query"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| get_text(renderer.get("suggestion")?, false, false))
+}
+
+/// Collects the suggested queries and the direct song and artist hits
+/// of a `get_search_suggestions` response.
+pub(crate) fn parse_search_suggestions(json: &Value) -> crate::types::Result<SearchSuggestions> {
+    let mut suggestions = SearchSuggestions {
+        suggestions: from_json(json, get_search_suggestion)?,
+        songs: Vec::new(),
+        artists: Vec::new(),
+    };
+    for item in from_json(json, get_search_item)? {
+        match item {
+            SearchItem::Song(e) | SearchItem::Video(e) => suggestions.songs.push(e),
+            SearchItem::Artist(e) => suggestions.artists.push(e),
+            _ => (),
+        }
+    }
+    Ok(suggestions)
+}
+
+/// Tries to extract a track from a `musicResponsiveListItemRenderer`.
+/// The second flex column is split on the ` • ` separators, linked runs are used
+/// to find the artists and the album, and the duration is taken from the segment
+/// that looks like `m:ss` or from the fixed columns.
+/// Playlists put the album in a column of its own, so it's looked for in the next ones too.
+pub(crate) fn get_video_from_item(renderer: &Value) -> Option<YoutubeMusicVideoRef> {
+    let columns = renderer.get("flexColumns")?.as_array()?;
+    let title_runs = get_column(columns.first()?)?;
+    let video_id = renderer
+        .get("playlistItemData")
+        .and_then(|x| x.get("videoId"))
+        .or_else(|| find_key(title_runs, "watchEndpoint").and_then(|x| x.get("videoId")))
+        .and_then(Value::as_str)?;
+    let title = get_text(title_runs, false, false)?;
+
+    let segments = split_segments(columns.get(1).and_then(get_column));
+
+    let author = segments
+        .iter()
+        .find(|(_, x)| {
+            matches!(
+                x,
+                Some("MUSIC_PAGE_TYPE_ARTIST" | "MUSIC_PAGE_TYPE_USER_CHANNEL")
+            )
+        })
+        .or_else(|| {
+            segments.iter().find(|(text, x)| {
+                x.is_none()
+                    && !text.is_empty()
+                    && !is_duration(text)
+                    && !matches!(text.as_str(), "Song" | "Video" | "Episode")
+            })
+        })
+        .map(|(text, _)| text.clone())
+        .unwrap_or_default();
+    let album = segments
+        .iter()
+        .cloned()
+        .chain(
+            columns
+                .iter()
+                .skip(2)
+                .flat_map(|x| split_segments(get_column(x))),
+        )
+        .find(|(_, x)| *x == Some("MUSIC_PAGE_TYPE_ALBUM"))
+        .map(|(text, _)| text)
+        .unwrap_or_default();
+    let duration = segments
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .find(|x| is_duration(x))
+        .map(str::to_string)
+        .or_else(|| {
+            renderer
+                .get("fixedColumns")?
+                .as_array()?
+                .iter()
+                .flat_map(|x| {
+                    x.get("musicResponsiveListItemFixedColumnRenderer")
+                        .and_then(|x| x.get("text"))
+                        .and_then(|x| get_text(x, false, false))
+                })
+                .find(|x| is_duration(x))
+        })
+        .unwrap_or_default();
+
+    Some(YoutubeMusicVideoRef {
+        title,
+        author,
+        album,
+        video_id: video_id.to_string(),
+        duration,
+    })
+}
+
+/// Splits the runs of a text object on the ` • ` separators.
+/// Each segment comes with the page type of its first linked run.
+fn split_segments(value: Option<&Value>) -> Vec<(String, Option<&str>)> {
+    let mut segments: Vec<(String, Option<&str>)> = vec![(String::new(), None)];
+    for run in value
+        .and_then(|x| x.get("runs"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let text = run.get("text").and_then(Value::as_str).unwrap_or_default();
+        if text == " • " {
+            segments.push((String::new(), None));
+            continue;
+        }
+        if let Some(segment) = segments.last_mut() {
+            segment.0.push_str(text);
+            if segment.1.is_none() {
+                segment.1 = find_key(run, "pageType").and_then(Value::as_str);
+            }
+        }
+    }
+    segments
+}
+
+/// Returns the name and the browse id of every run linking to an artist page
+fn get_artist_runs(value: Option<&Value>) -> Vec<YoutubeMusicArtistRef> {
+    value
+        .and_then(|x| x.get("runs"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|x| {
+            find_key(x, "pageType").and_then(Value::as_str) == Some("MUSIC_PAGE_TYPE_ARTIST")
+        })
+        .flat_map(|x| {
+            Some(YoutubeMusicArtistRef {
+                name: x.get("text")?.as_str()?.to_string(),
+                channel_id: find_key(x, "browseId")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Tries to extract the thumbnails of a header or an item, smallest first.
+/// Its own `thumbnail` comes before the nested ones, such as the avatar of the strapline.
+pub(crate) fn get_thumbnails(value: &Value) -> Vec<YoutubeMusicThumbnail> {
+    value
+        .get("thumbnail")
+        .and_then(|x| find_key(x, "thumbnails"))
+        .or_else(|| find_key(value, "thumbnails"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .flat_map(|x| {
+            Some(YoutubeMusicThumbnail {
+                url: x.get("url")?.as_str()?.to_string(),
+                width: x.get("width").and_then(Value::as_u64).unwrap_or_default() as u32,
+                height: x.get("height").and_then(Value::as_u64).unwrap_or_default() as u32,
+            })
+        })
+        .collect()
+}
+
+/// Parses an album page.
+/// Both the `musicResponsiveHeaderRenderer` and the older `musicDetailHeaderRenderer`
+/// headers are supported. Tracks without an artist column inherit the album artists.
+pub(crate) fn parse_album(json: &Value, browse_id: &str) -> Option<YoutubeMusicAlbum> {
+    let header = find_key(json, "musicResponsiveHeaderRenderer")
+        .or_else(|| find_key(json, "musicDetailHeaderRenderer"))?;
+    let title = get_text(header.get("title")?, false, false)?;
+    let subtitle = split_segments(header.get("subtitle"));
+    let year = subtitle
+        .iter()
+        .map(|(text, _)| text.trim())
+        .find(|x| x.len() == 4 && x.chars().all(|x| x.is_ascii_digit()))
+        .map(str::to_string);
+
+    let mut artists = get_artist_runs(header.get("straplineTextOne"));
+    if artists.is_empty() {
+        artists = get_artist_runs(header.get("subtitle"));
+    }
+    let artists_text = header
+        .get("straplineTextOne")
+        .and_then(|x| get_text(x, false, false))
+        .or_else(|| {
+            subtitle
+                .iter()
+                .find(|(_, x)| *x == Some("MUSIC_PAGE_TYPE_ARTIST"))
+                .map(|(text, _)| text.clone())
+        })
+        .unwrap_or_else(|| {
+            artists
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+
+    let tracks = from_json(json, |x| {
+        let renderer = x.get("musicResponsiveListItemRenderer")?;
+        let mut video = get_video_from_item(renderer)?;
+        if video.author.is_empty() {
+            video.author = artists_text.clone();
+        }
+        if video.album.is_empty() {
+            video.album = title.clone();
+        }
+        let number = renderer
+            .get("index")
+            .and_then(|x| get_text(x, false, false))
+            .and_then(|x| x.parse().ok());
+        Some(YoutubeMusicAlbumTrack { number, video })
+    })
+    .ok()?;
+
+    Some(YoutubeMusicAlbum {
+        browse_id: browse_id.to_string(),
+        title,
+        artists,
+        year,
+        thumbnails: get_thumbnails(header),
+        tracks,
+    })
+}
+
+/// Tries to extract a track from a `musicResponsiveListItemRenderer` wrapper
+pub(crate) fn get_list_item_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
+    get_video_from_item(value.get("musicResponsiveListItemRenderer")?)
+}
+
+/// Tries to extract a track of a playlist with its `playlistSetVideoId`,
+/// which tells apart the entries when the same video is added several times.
+pub(crate) fn get_playlist_item(value: &Value) -> Option<(Option<String>, YoutubeMusicVideoRef)> {
+    let renderer = value.get("musicResponsiveListItemRenderer")?;
+    let set_video_id = renderer
+        .get("playlistItemData")
+        .and_then(|x| x.get("playlistSetVideoId"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let video = get_video_from_item(renderer)
+        .or_else(|| get_video(renderer))
+        .or_else(|| get_video_from_album(renderer))?;
+    Some((set_video_id, video))
+}
+
+/// Tries to extract a video from a `musicTwoRowItemRenderer` wrapper.
+/// These come from carousels and only show the title, the artist and the view count.
+pub(crate) fn get_two_row_item_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
+    let renderer = value.get("musicTwoRowItemRenderer")?;
+    let video_id = renderer
+        .get("navigationEndpoint")?
+        .get("watchEndpoint")?
+        .get("videoId")?
+        .as_str()?;
+    let author = split_segments(renderer.get("subtitle"))
+        .into_iter()
+        .map(|(text, _)| text)
+        .find(|x| !x.is_empty())
+        .unwrap_or_default();
+    Some(YoutubeMusicVideoRef {
+        title: get_text(renderer.get("title")?, false, false)?,
+        author,
+        album: String::new(),
+        video_id: video_id.to_string(),
+        duration: String::new(),
+    })
+}
+
+/// Tries to extract a track from a `playlistPanelVideoRenderer` wrapper of the watch queue.
+/// When a song has a video counterpart, only the primary renderer is kept.
+pub(crate) fn get_panel_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
+    let renderer = value.get("playlistPanelVideoRenderer").or_else(|| {
+        value
+            .get("playlistPanelVideoWrapperRenderer")?
+            .get("primaryRenderer")?
+            .get("playlistPanelVideoRenderer")
+    })?;
+    let segments = split_segments(renderer.get("longBylineText"));
+    let author = segments
+        .iter()
+        .find(|(_, x)| *x == Some("MUSIC_PAGE_TYPE_ARTIST"))
+        .or_else(|| segments.first())
+        .map(|(text, _)| text.clone())
+        .unwrap_or_default();
+    let album = segments
+        .iter()
+        .find(|(_, x)| *x == Some("MUSIC_PAGE_TYPE_ALBUM"))
+        .map(|(text, _)| text.clone())
+        .unwrap_or_default();
+    Some(YoutubeMusicVideoRef {
+        title: get_text(renderer.get("title")?, false, false)?,
+        author,
+        album,
+        video_id: renderer.get("videoId")?.as_str()?.to_string(),
+        duration: renderer
+            .get("lengthText")
+            .and_then(|x| get_text(x, false, false))
+            .unwrap_or_default(),
+    })
+}
+
+/// Tries to extract an album, a single or an artist from a `musicTwoRowItemRenderer` wrapper
+pub(crate) fn get_two_row_item_playlist(value: &Value) -> Option<YoutubeMusicPlaylistRef> {
+    get_playlist(value.get("musicTwoRowItemRenderer")?)
+}
+
+/// Returns the browse endpoint of the "Show all" or "More" button of a shelf
+fn get_shelf_more(shelf: &Value) -> Option<ArtistShelfRef> {
+    let endpoint = ["bottomEndpoint", "title", "header"]
+        .iter()
+        .find_map(|key| find_key(shelf.get(key)?, "browseEndpoint"))?;
+    Some(ArtistShelfRef {
+        browse_id: endpoint.get("browseId")?.as_str()?.to_string(),
+        params: endpoint
+            .get("params")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    })
+}
+
+/// What the items of a carousel shelf open, read from its first item
+enum CarouselKind {
+    Videos,
+    Releases,
+    Artists,
+    Other,
+}
+
+fn get_carousel_kind(contents: &Value) -> CarouselKind {
+    let Some(endpoint) = contents
+        .as_array()
+        .and_then(|x| x.first())
+        .and_then(|x| find_key(x, "navigationEndpoint"))
+    else {
+        return CarouselKind::Other;
+    };
+    if endpoint.get("watchEndpoint").is_some() {
+        return CarouselKind::Videos;
+    }
+    match find_key(endpoint, "pageType").and_then(Value::as_str) {
+        Some("MUSIC_PAGE_TYPE_ALBUM") => CarouselKind::Releases,
+        Some("MUSIC_PAGE_TYPE_ARTIST") => CarouselKind::Artists,
+        _ => CarouselKind::Other,
+    }
+}
+
+/// Parses an artist page.
+/// The shelves are recognized by the kind of their items rather than by their translated title,
+/// the first shelf of releases holds the albums and the second one the singles.
+pub(crate) fn parse_artist(json: &Value, channel_id: &str) -> Option<YoutubeMusicArtist> {
+    let header = find_key(json, "musicImmersiveHeaderRenderer")
+        .or_else(|| find_key(json, "musicVisualHeaderRenderer"))?;
+    let mut artist = YoutubeMusicArtist {
+        channel_id: channel_id.to_string(),
+        name: get_text(header.get("title")?, false, false)?,
+        description: header
+            .get("description")
+            .and_then(|x| get_text(x, false, false)),
+        subscribers: find_key(header, "subscriberCountText")
+            .and_then(|x| get_text(x, false, false)),
+        thumbnails: get_thumbnails(header),
+        songs: ArtistShelf::default(),
+        albums: ArtistShelf::default(),
+        singles: ArtistShelf::default(),
+        videos: ArtistShelf::default(),
+        related: ArtistShelf::default(),
+    };
+
+    let sections = find_key(json, "sectionListRenderer")
+        .and_then(|x| x.get("contents"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten();
+    let mut release_shelves = 0;
+    for section in sections {
+        if let Some(shelf) = section.get("musicShelfRenderer") {
+            let contents = shelf.get("contents").unwrap_or(&Value::Null);
+            artist.songs = ArtistShelf {
+                items: from_json(contents, get_list_item_video).ok()?,
+                more: get_shelf_more(shelf),
+            };
+            continue;
+        }
+        let Some(shelf) = section.get("musicCarouselShelfRenderer") else {
+            continue;
+        };
+        let contents = shelf.get("contents").unwrap_or(&Value::Null);
+        let more = get_shelf_more(shelf);
+        match get_carousel_kind(contents) {
+            CarouselKind::Videos => {
+                artist.videos = ArtistShelf {
+                    items: from_json(contents, get_two_row_item_video).ok()?,
+                    more,
+                };
+            }
+            CarouselKind::Releases if release_shelves < 2 => {
+                let releases = ArtistShelf {
+                    items: from_json(contents, get_two_row_item_playlist).ok()?,
+                    more,
+                };
+                if release_shelves == 0 {
+                    artist.albums = releases;
+                } else {
+                    artist.singles = releases;
+                }
+                release_shelves += 1;
+            }
+            CarouselKind::Artists => {
+                artist.related = ArtistShelf {
+                    items: from_json(contents, get_two_row_item_playlist)
+                        .ok()?
+                        .into_iter()
+                        .map(|x| YoutubeMusicArtistRef {
+                            name: x.name,
+                            channel_id: x.browse_id,
+                        })
+                        .collect(),
+                    more,
+                };
+            }
+            CarouselKind::Releases | CarouselKind::Other => (),
+        }
+    }
+    Some(artist)
+}
+
+/// Parses the header of a playlist page, the tracks are left empty.
+pub(crate) fn parse_playlist_header(json: &Value, browse_id: &str) -> Option<YoutubeMusicPlaylist> {
+    let header = find_key(json, "musicResponsiveHeaderRenderer")
+        .or_else(|| find_key(json, "musicDetailHeaderRenderer"))?;
+    let title = get_text(header.get("title")?, false, false)?;
+    let subtitle = split_segments(header.get("subtitle"));
+    let owner = header
+        .get("straplineTextOne")
+        .and_then(|x| get_text(x, false, false))
+        .or_else(|| {
+            subtitle
+                .iter()
+                .find(|(_, x)| {
+                    matches!(
+                        x,
+                        Some("MUSIC_PAGE_TYPE_USER_CHANNEL" | "MUSIC_PAGE_TYPE_ARTIST")
+                    )
+                })
+                .or_else(|| subtitle.get(1))
+                .map(|(text, _)| text.clone())
+        });
+    let track_count = split_segments(header.get("secondSubtitle"))
+        .into_iter()
+        .chain(subtitle.iter().cloned())
+        .map(|(text, _)| text)
+        .find(|x| x.contains("song") || x.contains("track") || x.contains("episode"))
+        .and_then(|x| {
+            x.chars()
+                .filter(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .ok()
+        });
+    // The responsive header wraps the description in a shelf
+    let description = header
+        .get("description")
+        .map(|x| {
+            x.get("musicDescriptionShelfRenderer")
+                .and_then(|x| x.get("description"))
+                .unwrap_or(x)
+        })
+        .and_then(get_raw_text)
+        .filter(|x| !x.trim().is_empty());
+    let privacy = find_key(json, "privacy")
+        .and_then(Value::as_str)
+        .and_then(|x| match x {
+            "PUBLIC" => Some(PlaylistPrivacy::Public),
+            "UNLISTED" => Some(PlaylistPrivacy::Unlisted),
+            "PRIVATE" => Some(PlaylistPrivacy::Private),
+            _ => None,
+        });
+
+    Some(YoutubeMusicPlaylist {
+        browse_id: browse_id.to_string(),
+        title,
+        owner,
+        track_count,
+        description,
+        privacy,
+        thumbnails: get_thumbnails(header),
+        tracks: Vec::new(),
+    })
+}
+
+/// Tries to find the browse id of the lyrics tab in a `next` response
+pub(crate) fn get_lyrics_browse_id(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(e) => e.iter().find_map(get_lyrics_browse_id),
+        Value::Object(e) => e
+            .get("browseEndpoint")
+            .and_then(|x| x.get("browseId"))
+            .and_then(Value::as_str)
+            .filter(|x| x.starts_with("MPLY"))
+            .map(str::to_string)
+            .or_else(|| e.values().find_map(get_lyrics_browse_id)),
+        _ => None,
+    }
+}
+
+/// Parses a lyrics page.
+/// The plain text comes from the description shelf, the timed lines are only
+/// present when the provider synced them.
+pub(crate) fn parse_lyrics(json: &Value) -> Option<YoutubeMusicLyrics> {
+    let timed = find_key(json, "timedLyricsData")
+        .and_then(Value::as_array)
+        .map(|lines| {
+            lines
+                .iter()
+                .flat_map(|x| {
+                    let cue = x.get("cueRange")?;
+                    let time = |key| match cue.get(key)? {
+                        Value::String(e) => e.parse().ok(),
+                        e => e.as_u64(),
+                    };
+                    Some(TimedLyricsLine {
+                        text: x.get("lyricLine")?.as_str()?.to_string(),
+                        start_ms: time("startTimeMilliseconds")?,
+                        end_ms: time("endTimeMilliseconds")?,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|x| !x.is_empty());
+    let shelf = find_key(json, "musicDescriptionShelfRenderer");
+    let text = shelf
+        .and_then(|x| x.get("description"))
+        .and_then(get_raw_text)
+        .or_else(|| {
+            timed.as_ref().map(|x| {
+                x.iter()
+                    .map(|x| x.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        })?;
+    let source = shelf
+        .and_then(|x| x.get("footer"))
+        .and_then(get_raw_text)
+        .or_else(|| {
+            find_key(json, "sourceMessage")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        });
+    Some(YoutubeMusicLyrics {
+        text,
+        source,
+        timed,
+    })
+}
+
+/// Concatenates the runs of a text object without cleaning them, to keep the line breaks
+fn get_raw_text(value: &Value) -> Option<String> {
+    if let Some(e) = value.as_str() {
+        return Some(e.to_string());
+    }
+    let text = value
+        .get("runs")?
+        .as_array()?
+        .iter()
+        .flat_map(|x| x.get("text").and_then(Value::as_str))
+        .collect::<String>();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Reads the playability status of a `player` response.
+/// Returns `None` when the video can be played.
+pub(crate) fn get_playability_status(json: &Value) -> Option<PlayabilityStatus> {
+    let playability = json.get("playabilityStatus")?;
+    let status = playability.get("status")?.as_str()?;
+    let reason = playability
+        .get("reason")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    match status {
+        "OK" => None,
+        "LOGIN_REQUIRED" => Some(PlayabilityStatus::LoginRequired(reason)),
+        "AGE_CHECK_REQUIRED" | "AGE_VERIFICATION_REQUIRED" | "CONTENT_CHECK_REQUIRED" => {
+            Some(PlayabilityStatus::AgeRestricted(reason))
+        }
+        "UNPLAYABLE" => Some(PlayabilityStatus::Unplayable(reason)),
+        "ERROR" => Some(PlayabilityStatus::Error(reason)),
+        e => Some(PlayabilityStatus::Other(e.to_string(), reason)),
+    }
+}
+
+/// Parses the audio formats of a `player` response, video only formats are skipped.
+pub(crate) fn parse_player(json: &Value, video_id: &str) -> YoutubeMusicPlayer {
+    // Numbers are sometimes sent as strings
+    let number = |x: &Value| match x {
+        Value::String(e) => e.parse().ok(),
+        e => e.as_u64(),
+    };
+    let formats = json
+        .get("streamingData")
+        .and_then(|x| x.get("adaptiveFormats"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|x| {
+            x.get("mimeType")
+                .and_then(Value::as_str)
+                .is_some_and(|x| x.starts_with("audio/"))
+        })
+        .flat_map(|x| {
+            Some(YoutubeMusicAudioFormat {
+                itag: x.get("itag").and_then(number)? as u32,
+                mime_type: x.get("mimeType")?.as_str()?.to_string(),
+                bitrate: x.get("bitrate").and_then(number).unwrap_or_default(),
+                content_length: x.get("contentLength").and_then(number),
+                loudness_db: x.get("loudnessDb").and_then(Value::as_f64),
+                url: x.get("url").and_then(Value::as_str).map(str::to_string),
+                signature_cipher: x
+                    .get("signatureCipher")
+                    .or_else(|| x.get("cipher"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            })
+        })
+        .collect::<Vec<_>>();
+    let loudness_db = json
+        .get("playerConfig")
+        .and_then(|x| x.get("audioConfig"))
+        .and_then(|x| x.get("loudnessDb"))
+        .and_then(Value::as_f64)
+        .or_else(|| formats.iter().find_map(|x| x.loudness_db));
+    YoutubeMusicPlayer {
+        video_id: video_id.to_string(),
+        duration_seconds: json
+            .get("videoDetails")
+            .and_then(|x| x.get("lengthSeconds"))
+            .and_then(number),
+        loudness_db,
+        formats,
+    }
+}
+
+/// Returns the text object of a flex column of a `musicResponsiveListItemRenderer`
+fn get_column(value: &Value) -> Option<&Value> {
+    value
+        .get("musicResponsiveListItemFlexColumnRenderer")
+        .and_then(|x| x.get("text"))
+}
+
+/// Checks if the text is a duration in the `h:mm:ss` or `m:ss` format
+fn is_duration(text: &str) -> bool {
+    text.contains(':') && text.chars().all(|x| x.is_ascii_digit() || x == ':')
+}
+
+/// Tries to find the first value associated to the key in the json
+fn find_key<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Array(e) => e.iter().find_map(|x| find_key(x, key)),
+        Value::Object(e) => e
+            .get(key)
+            .or_else(|| e.values().find_map(|x| find_key(x, key))),
+        _ => None,
+    }
+}
+
 pub fn extract_playlist_info(value: &Value) -> Option<(String, String)> {
     let header = value.get("header")?.get("musicDetailHeaderRenderer")?;
     let title = get_text(header.get("title")?, false, false)?;
@@ -215,3 +963,430 @@ pub(crate) fn get_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
         duration: String::new(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Responses of YouTube Music reduced to the renderers the parsers read
+    const SEARCH: &str = include_str!("../fixtures/search.json");
+    const ALBUM: &str = include_str!("../fixtures/album.json");
+    const ARTIST: &str = include_str!("../fixtures/artist.json");
+    const PLAYLIST: &str = include_str!("../fixtures/playlist.json");
+    const NEXT: &str = include_str!("../fixtures/next.json");
+    const LYRICS: &str = include_str!("../fixtures/lyrics.json");
+    const TIMED_LYRICS: &str = include_str!("../fixtures/lyrics_timed.json");
+    const PLAYER: &str = include_str!("../fixtures/player.json");
+    const UNPLAYABLE: &str = include_str!("../fixtures/player_unplayable.json");
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn video(
+        title: &str,
+        author: &str,
+        album: &str,
+        video_id: &str,
+        duration: &str,
+    ) -> YoutubeMusicVideoRef {
+        YoutubeMusicVideoRef {
+            title: title.to_string(),
+            author: author.to_string(),
+            album: album.to_string(),
+            video_id: video_id.to_string(),
+            duration: duration.to_string(),
+        }
+    }
+
+    fn artist(name: &str, channel_id: &str) -> YoutubeMusicArtistRef {
+        YoutubeMusicArtistRef {
+            name: name.to_string(),
+            channel_id: channel_id.to_string(),
+        }
+    }
+
+    fn playlist(name: &str, subtitle: &str, browse_id: &str) -> YoutubeMusicPlaylistRef {
+        YoutubeMusicPlaylistRef {
+            name: name.to_string(),
+            subtitle: subtitle.to_string(),
+            browse_id: browse_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_search_results() {
+        let json = fixture(SEARCH);
+        let continuations = from_json(&json, get_continuation).unwrap();
+        assert_eq!(
+            continuations,
+            [Continuation {
+                continuation: "EpIDEgVxdWVlbhqIA0Vnb".to_string(),
+                click_tracking_params: "CBQQybcCIhMI".to_string(),
+            }]
+        );
+        let results = parse_search_results(&json, continuations.clone()).unwrap();
+        // The top result is the first song again
+        assert_eq!(
+            results.songs,
+            [video(
+                "Bohemian Rhapsody",
+                "Queen",
+                "A Night at the Opera",
+                "fJ9rUzIMcZQ",
+                "5:55"
+            )]
+        );
+        assert_eq!(
+            results.videos,
+            [video(
+                "Queen – Bohemian Rhapsody (Official Video Remastered)",
+                "Queen Official",
+                "",
+                "Sx0aIn9TjKo",
+                "6:00"
+            )]
+        );
+        assert_eq!(
+            results.albums,
+            [playlist(
+                "A Night at the Opera",
+                "Album • Queen • 1975",
+                "MPREb_3ZyJnUvmvHG"
+            )]
+        );
+        assert_eq!(
+            results.artists,
+            [playlist(
+                "Queen",
+                "Artist • 30.2M subscribers",
+                "UCiMhD4jzUqG-IgPzUmmytRQ"
+            )]
+        );
+        // Without a page type, the kind is read from the browse id
+        assert_eq!(
+            results.playlists,
+            [playlist(
+                "Queen Greatest Hits",
+                "Playlist • Queen Fan • 120 songs",
+                "VLPLxRJqrRmSzB2kA9xHJAVuDNrq6dC7aRZt"
+            )]
+        );
+        assert_eq!(results.continuations, continuations);
+    }
+
+    #[test]
+    fn parses_album() {
+        let album = parse_album(&fixture(ALBUM), "MPREb_3ZyJnUvmvHG").unwrap();
+        assert_eq!(album.browse_id, "MPREb_3ZyJnUvmvHG");
+        assert_eq!(album.title, "A Night at the Opera");
+        assert_eq!(album.artists, [artist("Queen", "UCiMhD4jzUqG-IgPzUmmytRQ")]);
+        assert_eq!(album.year.as_deref(), Some("1975"));
+        assert_eq!(
+            album.thumbnails,
+            [
+                YoutubeMusicThumbnail {
+                    url: "https://lh3.googleusercontent.com/opera=w60-h60".to_string(),
+                    width: 60,
+                    height: 60,
+                },
+                YoutubeMusicThumbnail {
+                    url: "https://lh3.googleusercontent.com/opera=w544-h544".to_string(),
+                    width: 544,
+                    height: 544,
+                },
+            ]
+        );
+        // The tracks without an artist column get the artists of the album
+        let track = |number, title, video_id, duration| YoutubeMusicAlbumTrack {
+            number: Some(number),
+            video: video(title, "Queen", "A Night at the Opera", video_id, duration),
+        };
+        assert_eq!(
+            album.tracks,
+            [
+                track(
+                    1,
+                    "Death on Two Legs (Dedicated to...)",
+                    "Ob8gkQgFgqY",
+                    "3:43"
+                ),
+                track(2, "Lazing on a Sunday Afternoon", "5VEyaE2Ifmo", "1:08"),
+                track(11, "Bohemian Rhapsody", "fJ9rUzIMcZQ", "5:55"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_album_with_detail_header() {
+        let json = serde_json::json!({
+            "header": {"musicDetailHeaderRenderer": {
+                "title": {"runs": [{"text": "Jazz"}]},
+                "subtitle": {"runs": [
+                    {"text": "Album"},
+                    {"text": " • "},
+                    {"text": "Queen", "navigationEndpoint": {"browseEndpoint": {
+                        "browseId": "UCiMhD4jzUqG-IgPzUmmytRQ",
+                        "browseEndpointContextSupportedConfigs": {"browseEndpointContextMusicConfig": {
+                            "pageType": "MUSIC_PAGE_TYPE_ARTIST"
+                        }}
+                    }}},
+                    {"text": " • "},
+                    {"text": "1978"}
+                ]}
+            }}
+        });
+        let album = parse_album(&json, "MPREb_jazz").unwrap();
+        assert_eq!(album.title, "Jazz");
+        assert_eq!(album.artists, [artist("Queen", "UCiMhD4jzUqG-IgPzUmmytRQ")]);
+        assert_eq!(album.year.as_deref(), Some("1978"));
+        assert!(album.tracks.is_empty());
+        assert!(parse_album(&fixture(SEARCH), "MPREb_3ZyJnUvmvHG").is_none());
+    }
+
+    #[test]
+    fn parses_artist() {
+        let page = parse_artist(&fixture(ARTIST), "UCiMhD4jzUqG-IgPzUmmytRQ").unwrap();
+        let more = |browse_id: &str, params: Option<&str>| {
+            Some(ArtistShelfRef {
+                browse_id: browse_id.to_string(),
+                params: params.map(str::to_string),
+            })
+        };
+        assert_eq!(page.channel_id, "UCiMhD4jzUqG-IgPzUmmytRQ");
+        assert_eq!(page.name, "Queen");
+        assert_eq!(
+            page.description.as_deref(),
+            Some("Queen are a British rock band formed in London in 1970.")
+        );
+        assert_eq!(page.subscribers.as_deref(), Some("30.2M"));
+        assert_eq!(page.thumbnails.len(), 2);
+        assert_eq!(
+            page.songs,
+            ArtistShelf {
+                items: vec![
+                    video("Bohemian Rhapsody", "Queen", "", "fJ9rUzIMcZQ", ""),
+                    video("Don't Stop Me Now", "Queen", "", "HgzGwKwLmgM", ""),
+                ],
+                more: more("VLOLAK5uy_kbqmtUI4sFkLK6u3KQX6rsydcjjEWkwlU", None),
+            }
+        );
+        assert_eq!(
+            page.albums,
+            ArtistShelf {
+                items: vec![
+                    playlist("A Night at the Opera", "Album • 1975", "MPREb_3ZyJnUvmvHG"),
+                    playlist("News of the World", "Album • 1977", "MPREb_7nJUZ28HSr0"),
+                ],
+                more: more("MPADUCiMhD4jzUqG-IgPzUmmytRQ", Some("ggMIegYIARoCAQI%3D")),
+            }
+        );
+        assert_eq!(
+            page.singles,
+            ArtistShelf {
+                items: vec![playlist(
+                    "Face It Alone",
+                    "Single • 2022",
+                    "MPREb_u1I69lSAe5v"
+                )],
+                more: more("MPADUCiMhD4jzUqG-IgPzUmmytRQ", Some("ggMIegYIAhoCAQI%3D")),
+            }
+        );
+        assert_eq!(
+            page.videos,
+            ArtistShelf {
+                items: vec![video(
+                    "Bohemian Rhapsody (Official Video Remastered)",
+                    "Queen",
+                    "",
+                    "Sx0aIn9TjKo",
+                    ""
+                )],
+                more: more("VLPL1F8i6l0bO4AnfRcOUJjA1BFkA5oXR5e2", None),
+            }
+        );
+        // The playlists and the releases after the singles are left out
+        assert_eq!(
+            page.related,
+            ArtistShelf {
+                items: vec![
+                    artist("David Bowie", "UC8YgWcDKi1rLbQ1OtrOHeDw"),
+                    artist("Led Zeppelin", "UCFlgZKWWvXW1f9H1qWQrLQQ"),
+                ],
+                more: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_playlist() {
+        let json = fixture(PLAYLIST);
+        let header = parse_playlist_header(&json, "VLPLxRJqrRmSzB2kA9xHJAVuDNrq6dC7aRZt").unwrap();
+        assert_eq!(header.browse_id, "VLPLxRJqrRmSzB2kA9xHJAVuDNrq6dC7aRZt");
+        assert_eq!(header.title, "Queen Greatest Hits");
+        assert_eq!(header.owner.as_deref(), Some("Queen Fan"));
+        assert_eq!(header.track_count, Some(1024));
+        assert_eq!(
+            header.description.as_deref(),
+            Some("The best of Queen.\nUpdated every week.")
+        );
+        assert_eq!(header.privacy, Some(PlaylistPrivacy::Unlisted));
+        assert_eq!(
+            header.thumbnails.first().map(|x| x.url.as_str()),
+            Some("https://lh3.googleusercontent.com/greatest-hits=w60-h60")
+        );
+        assert!(header.tracks.is_empty());
+
+        // A video added twice is kept twice, with another `playlistSetVideoId`
+        let rhapsody = video(
+            "Bohemian Rhapsody",
+            "Queen",
+            "A Night at the Opera",
+            "fJ9rUzIMcZQ",
+            "5:55",
+        );
+        assert_eq!(
+            from_json(&json, get_playlist_item).unwrap(),
+            [
+                (Some("56B44F6D10557CC6".to_string()), rhapsody.clone()),
+                (
+                    Some("2089F6B5A4A3A55C".to_string()),
+                    video("Don't Stop Me Now", "Queen", "Jazz", "HgzGwKwLmgM", "3:30")
+                ),
+                (Some("5A9B4B3F2C1D0E9F".to_string()), rhapsody),
+            ]
+        );
+        assert_eq!(
+            from_json(&json, get_continuation).unwrap(),
+            [Continuation {
+                continuation: "4qmFsgJbEiRWTFBMeFJK".to_string(),
+                click_tracking_params: "CBwQ7zsYACITCN".to_string(),
+            }]
+        );
+    }
+    #[test]
+    fn finds_lyrics_tab() {
+        assert_eq!(
+            get_lyrics_browse_id(&fixture(NEXT)).as_deref(),
+            Some("MPLYt_fJ9rUzIMcZQ-1")
+        );
+        assert_eq!(get_lyrics_browse_id(&fixture(LYRICS)), None);
+    }
+
+    #[test]
+    fn parses_lyrics() {
+        assert_eq!(
+            parse_lyrics(&fixture(LYRICS)),
+            Some(YoutubeMusicLyrics {
+                text: "Is this the real life?\nIs this just fantasy?\n\nCaught in a landslide"
+                    .to_string(),
+                source: Some("Source: Musixmatch".to_string()),
+                timed: None,
+            })
+        );
+        assert_eq!(parse_lyrics(&fixture(NEXT)), None);
+    }
+
+    #[test]
+    fn parses_timed_lyrics() {
+        // The times are strings, or numbers in older responses
+        let line = |text: &str, start_ms, end_ms| TimedLyricsLine {
+            text: text.to_string(),
+            start_ms,
+            end_ms,
+        };
+        assert_eq!(
+            parse_lyrics(&fixture(TIMED_LYRICS)),
+            Some(YoutubeMusicLyrics {
+                text: "Is this the real life?\nIs this just fantasy?\nCaught in a landslide"
+                    .to_string(),
+                source: Some("Source: LyricFind".to_string()),
+                timed: Some(vec![
+                    line("Is this the real life?", 540, 3350),
+                    line("Is this just fantasy?", 3350, 6810),
+                    line("Caught in a landslide", 6810, 9620),
+                ]),
+            })
+        );
+    }
+    #[test]
+    fn parses_player() {
+        let json = fixture(PLAYER);
+        assert_eq!(get_playability_status(&json), None);
+        // The video formats are skipped, the numbers can be strings
+        assert_eq!(
+            parse_player(&json, "fJ9rUzIMcZQ"),
+            YoutubeMusicPlayer {
+                video_id: "fJ9rUzIMcZQ".to_string(),
+                duration_seconds: Some(355),
+                loudness_db: Some(-6.8),
+                formats: vec![
+                    YoutubeMusicAudioFormat {
+                        itag: 140,
+                        mime_type: "audio/mp4; codecs=\"mp4a.40.2\"".to_string(),
+                        bitrate: 130685,
+                        content_length: Some(5748282),
+                        loudness_db: Some(-7.2),
+                        url: Some(
+                            "https://rr1---sn-example.googlevideo.com/videoplayback?itag=140"
+                                .to_string()
+                        ),
+                        signature_cipher: None,
+                    },
+                    YoutubeMusicAudioFormat {
+                        itag: 251,
+                        mime_type: "audio/webm; codecs=\"opus\"".to_string(),
+                        bitrate: 158962,
+                        content_length: Some(6097517),
+                        loudness_db: Some(-7.2),
+                        url: None,
+                        signature_cipher: Some(
+                            "s=AOq0QJ8wRgIhAKEm&sp=sig&url=https://rr1---sn-example.googlevideo.com/videoplayback%3Fitag%3D251"
+                                .to_string()
+                        ),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn reads_playability_status() {
+        assert_eq!(
+            get_playability_status(&fixture(UNPLAYABLE)),
+            Some(PlayabilityStatus::LoginRequired(
+                "Sign in to confirm you're not a bot".to_string()
+            ))
+        );
+        let status = |status: &str| {
+            get_playability_status(&serde_json::json!({
+                "playabilityStatus": {"status": status, "reason": "Reason"}
+            }))
+        };
+        let reason = "Reason".to_string();
+        assert_eq!(
+            status("AGE_CHECK_REQUIRED"),
+            Some(PlayabilityStatus::AgeRestricted(reason.clone()))
+        );
+        assert_eq!(
+            status("UNPLAYABLE"),
+            Some(PlayabilityStatus::Unplayable(reason.clone()))
+        );
+        assert_eq!(
+            status("ERROR"),
+            Some(PlayabilityStatus::Error(reason.clone()))
+        );
+        assert_eq!(
+            status("LIVE_STREAM_OFFLINE"),
+            Some(PlayabilityStatus::Other(
+                "LIVE_STREAM_OFFLINE".to_string(),
+                reason
+            ))
+        );
+        // Without a player the formats and the duration are empty
+        let player = parse_player(&fixture(UNPLAYABLE), "fJ9rUzIMcZQ");
+        assert!(player.formats.is_empty());
+        assert_eq!(player.duration_seconds, None);
+        assert_eq!(player.loudness_db, None);
+    }
+}
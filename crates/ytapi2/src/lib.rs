@@ -1,3 +1,4 @@
+pub mod decipher;
 pub mod endpoint;
 pub mod instance;
 pub mod json_extractor;
@@ -18,6 +18,32 @@ pub enum YoutubeMusicError {
     IoError(std::io::Error),
     YoutubeMusicError(Value),
     InvalidHeaders,
+    NotPlayable(PlayabilityStatus),
+    CantFindPlayerUrl,
+    CantDecipher(String),
+}
+
+/// Why the player endpoint refused to give the streams of a video
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayabilityStatus {
+    LoginRequired(String),
+    AgeRestricted(String),
+    Unplayable(String),
+    Error(String),
+    /// Unknown status with its reason
+    Other(String, String),
+}
+
+impl Display for PlayabilityStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlayabilityStatus::LoginRequired(reason) => write!(f, "Login required: {reason}"),
+            PlayabilityStatus::AgeRestricted(reason) => write!(f, "Age restricted: {reason}"),
+            PlayabilityStatus::Unplayable(reason) => write!(f, "Unplayable: {reason}"),
+            PlayabilityStatus::Error(reason) => write!(f, "Error: {reason}"),
+            PlayabilityStatus::Other(status, reason) => write!(f, "{status}: {reason}"),
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, YoutubeMusicError>;
@@ -44,10 +70,148 @@ pub struct YoutubeMusicPlaylistRef {
     pub browse_id: String,
 }
 
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicArtistRef {
+    pub name: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicThumbnail {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicAlbumTrack {
+    /// Position of the track on the album, when the page shows it
+    pub number: Option<u32>,
+    pub video: YoutubeMusicVideoRef,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicAlbum {
+    pub browse_id: String,
+    pub title: String,
+    pub artists: Vec<YoutubeMusicArtistRef>,
+    pub year: Option<String>,
+    pub thumbnails: Vec<YoutubeMusicThumbnail>,
+    pub tracks: Vec<YoutubeMusicAlbumTrack>,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub enum PlaylistPrivacy {
+    Public,
+    Unlisted,
+    Private,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicPlaylist {
+    pub browse_id: String,
+    pub title: String,
+    pub owner: Option<String>,
+    /// Number of tracks announced by the header, which can differ from `tracks.len()`
+    /// when some tracks are unavailable
+    pub track_count: Option<u32>,
+    pub description: Option<String>,
+    /// Only known for the playlists owned by the logged in account
+    pub privacy: Option<PlaylistPrivacy>,
+    pub thumbnails: Vec<YoutubeMusicThumbnail>,
+    pub tracks: Vec<YoutubeMusicVideoRef>,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TimedLyricsLine {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicLyrics {
+    pub text: String,
+    /// Attribution of the lyrics provider, ie `Source: LyricFind`
+    pub source: Option<String>,
+    /// Only present when the provider synced the lyrics with the song
+    pub timed: Option<Vec<TimedLyricsLine>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct YoutubeMusicAudioFormat {
+    pub itag: u32,
+    pub mime_type: String,
+    pub bitrate: u64,
+    pub content_length: Option<u64>,
+    pub loudness_db: Option<f64>,
+    /// Direct url of the stream, when it doesn't need to be deciphered
+    pub url: Option<String>,
+    /// Url and signature to decipher, when `url` is missing
+    pub signature_cipher: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct YoutubeMusicPlayer {
+    pub video_id: String,
+    pub duration_seconds: Option<u64>,
+    /// Loudness of the track compared to the YouTube reference level
+    pub loudness_db: Option<f64>,
+    pub formats: Vec<YoutubeMusicAudioFormat>,
+}
+
+/// Browse endpoint of the "Show all" / "More" button of an artist shelf
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ArtistShelfRef {
+    pub browse_id: String,
+    pub params: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ArtistShelf<T> {
+    pub items: Vec<T>,
+    /// Where to fetch the full content of the shelf, if the page only shows a preview
+    pub more: Option<ArtistShelfRef>,
+}
+
+impl<T> Default for ArtistShelf<T> {
+    fn default() -> Self {
+        Self {
+            items: Vec::new(),
+            more: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub struct YoutubeMusicArtist {
+    pub channel_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub subscribers: Option<String>,
+    pub thumbnails: Vec<YoutubeMusicThumbnail>,
+    pub songs: ArtistShelf<YoutubeMusicVideoRef>,
+    pub albums: ArtistShelf<YoutubeMusicPlaylistRef>,
+    pub singles: ArtistShelf<YoutubeMusicPlaylistRef>,
+    pub videos: ArtistShelf<YoutubeMusicVideoRef>,
+    pub related: ArtistShelf<YoutubeMusicArtistRef>,
+}
+
 #[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash)]
 pub struct SearchResults {
-    pub videos: Vec<YoutubeMusicPlaylistRef>,
+    pub songs: Vec<YoutubeMusicVideoRef>,
+    pub videos: Vec<YoutubeMusicVideoRef>,
+    pub albums: Vec<YoutubeMusicPlaylistRef>,
+    pub artists: Vec<YoutubeMusicPlaylistRef>,
     pub playlists: Vec<YoutubeMusicPlaylistRef>,
+    pub continuations: Vec<Continuation>,
+}
+
+#[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash)]
+pub struct SearchSuggestions {
+    pub suggestions: Vec<String>,
+    pub songs: Vec<YoutubeMusicVideoRef>,
+    pub artists: Vec<YoutubeMusicPlaylistRef>,
 }
 
 #[derive(Debug, Clone, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
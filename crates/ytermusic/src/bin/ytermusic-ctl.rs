@@ -0,0 +1,394 @@
+//! Sends commands to a running ytermusic, for the status bars and the window manager bindings
+
+use std::process::ExitCode;
+
+use crate::protocol::{Command, Response, Status};
+
+#[path = "../protocol.rs"]
+mod protocol;
+#[path = "../utils.rs"]
+mod utils;
+
+const USAGE: &str = r#"Usage: ytermusic-ctl <command>
+
+Controls a running ytermusic, started with or without a terminal.
+
+Commands:
+        play [video id]     Resume the playback, or play a video right away
+        pause               Pause the playback
+        toggle              Pause or resume the playback
+        next                Play the next video of the queue
+        prev                Play the previous video of the queue
+        seek <[+-]seconds>  Move to a position, or by an offset with a sign
+        volume <[+-]pct>    Set the volume, or change it with a sign
+        queue add <ids...>  Add videos at the end of the queue
+        queue list [--json] Show the videos of the queue, `*` marks the current one
+        search <query>      Show the ids of the local musics and YouTube Music results
+        now-playing [--json | --format <format>]
+                            Show the current video, "{artist} - {title}" by default
+
+Placeholders of the format:
+        {title} {artist} {album} {video_id} {position} {duration}
+        {state} {volume} {repeat} {shuffle}
+
+Exit status:
+        0  Success
+        1  The command was refused by ytermusic
+        2  Invalid usage
+        3  No running ytermusic
+        4  Nothing is playing, for now-playing
+"#;
+
+const DEFAULT_FORMAT: &str = "{artist} - {title}";
+
+/// Why a command didn't succeed, each one has its own exit status
+enum Failure {
+    Refused(String),
+    Usage(String),
+    NoInstance(String),
+    /// Nothing to print, a status bar hides the block
+    Stopped,
+}
+
+impl Failure {
+    fn code(&self) -> u8 {
+        match self {
+            Failure::Refused(_) => 1,
+            Failure::Usage(_) => 2,
+            Failure::NoInstance(_) => 3,
+            Failure::Stopped => 4,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            match &failure {
+                Failure::Refused(message) | Failure::NoInstance(message) => {
+                    eprintln!("{message}")
+                }
+                Failure::Usage(message) => eprintln!("{message}\n\n{USAGE}"),
+                Failure::Stopped => (),
+            }
+            ExitCode::from(failure.code())
+        }
+    }
+}
+
+fn run(args: &[&str]) -> Result<(), Failure> {
+    match args {
+        ["-h" | "--help" | "help"] => {
+            print!("{USAGE}");
+            Ok(())
+        }
+        ["play"] => execute(Command::Play { video_id: None }),
+        ["play", video_id] => execute(Command::Play {
+            video_id: Some(video_id.to_string()),
+        }),
+        ["pause"] => execute(Command::Pause),
+        ["toggle"] => execute(Command::Toggle),
+        ["next"] => execute(Command::Next),
+        ["prev" | "previous"] => execute(Command::Previous),
+        ["seek", offset] => {
+            let (seconds, relative) = parse_offset(offset)?;
+            execute(Command::Seek { seconds, relative })
+        }
+        ["volume", offset] => {
+            let (percent, relative) = parse_offset(offset)?;
+            execute(Command::Volume { percent, relative })
+        }
+        ["queue", "add", video_ids @ ..] if !video_ids.is_empty() => execute(Command::Enqueue {
+            video_ids: video_ids.iter().map(|x| x.to_string()).collect(),
+        }),
+        ["queue"] | ["queue", "list"] => print_queue(false),
+        ["queue", "list", "--json"] => print_queue(true),
+        ["search", query @ ..] if !query.is_empty() => search(query.join(" ")),
+        ["now-playing"] => now_playing(None),
+        ["now-playing", "--json"] => now_playing(Some(None)),
+        ["now-playing", "--format", format] => now_playing(Some(Some(format))),
+        [] => Err(Failure::Usage("Missing command".to_string())),
+        _ => Err(Failure::Usage(format!(
+            "Invalid command `{}`",
+            args.join(" ")
+        ))),
+    }
+}
+
+/// `+5` and `-5` are offsets, `5` is absolute
+fn parse_offset<T: std::str::FromStr>(value: &str) -> Result<(T, bool), Failure> {
+    let relative = value.starts_with(['+', '-']);
+    value
+        .trim_start_matches('+')
+        .parse()
+        .map(|x| (x, relative))
+        .map_err(|_| Failure::Usage(format!("Invalid number `{value}`")))
+}
+
+/// For the commands answered with `done`
+fn execute(command: Command) -> Result<(), Failure> {
+    match request(&command)? {
+        Response::Done => Ok(()),
+        response => Err(unexpected(response)),
+    }
+}
+
+fn print_queue(json: bool) -> Result<(), Failure> {
+    let response = request(&Command::Queue)?;
+    let Response::Queue { videos, index, .. } = response else {
+        return Err(unexpected(response));
+    };
+    if json {
+        let json = serde_json::json!({ "videos": videos, "index": index });
+        println!("{json}");
+        return Ok(());
+    }
+    for (i, video) in videos.iter().enumerate() {
+        let marker = if index == Some(i) { '*' } else { ' ' };
+        println!("{marker} {:>3}  {}  {video}", i + 1, video.video_id);
+    }
+    Ok(())
+}
+
+/// One video per line, its id first so it can be passed to `queue add`
+fn search(query: String) -> Result<(), Failure> {
+    let response = request(&Command::Search { query })?;
+    let Response::Videos { videos } = response else {
+        return Err(unexpected(response));
+    };
+    for video in videos {
+        println!("{}  {video}", video.video_id);
+    }
+    Ok(())
+}
+
+/// `None` prints with the default format, `Some(None)` prints the status as JSON
+fn now_playing(output: Option<Option<&str>>) -> Result<(), Failure> {
+    let response = request(&Command::Status)?;
+    let Response::Status(status) = response else {
+        return Err(unexpected(response));
+    };
+    // The status is printed even when stopped, the volume and the queue stay useful
+    if output == Some(None) {
+        println!("{}", serde_json::to_string(&status).unwrap());
+    }
+    if status.video.is_none() {
+        return Err(Failure::Stopped);
+    }
+    if let Some(format) = output.unwrap_or(Some(DEFAULT_FORMAT)) {
+        println!("{}", format_status(format, &status));
+    }
+    Ok(())
+}
+
+/// Replaces the placeholders, the unknown ones are left as they are.
+/// The format is read once, so a title containing `{artist}` is printed as it is.
+fn format_status(format: &str, status: &Status) -> String {
+    let mut output = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest
+            .find('}')
+            .and_then(|end| Some((end, get_placeholder(&rest[1..end], status)?)));
+        match value {
+            Some((end, value)) => {
+                output.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Value of a placeholder of the format, by its name without the braces
+fn get_placeholder(name: &str, status: &Status) -> Option<String> {
+    let video = status.video.as_ref();
+    let field = |get: fn(&ytapi2::types::YoutubeMusicVideoRef) -> &String| {
+        video.map(get).cloned().unwrap_or_default()
+    };
+    Some(match name {
+        "title" => field(|x| &x.title),
+        "artist" => field(|x| &x.author),
+        "album" => field(|x| &x.album),
+        "video_id" => field(|x| &x.video_id),
+        "position" => format_time(status.position),
+        "duration" => status.duration.map(format_time).unwrap_or_default(),
+        "state" if status.loading => "loading".to_string(),
+        "state" if status.paused => "paused".to_string(),
+        "state" => "playing".to_string(),
+        "volume" => status.volume.to_string(),
+        "repeat" => status.repeat.clone(),
+        "shuffle" => if status.shuffle { "on" } else { "off" }.to_string(),
+        _ => return None,
+    })
+}
+
+/// Formats seconds as `m:ss`, or `h:mm:ss` past an hour
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+fn unexpected(response: Response) -> Failure {
+    Failure::Refused(format!("Unexpected response: {response:?}"))
+}
+
+/// Sends a command on the socket and waits for its response
+#[cfg(unix)]
+fn request(command: &Command) -> Result<Response, Failure> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+        time::Duration,
+    };
+
+    // A search can wait for YouTube Music, the other commands are answered right away
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    let path = protocol::get_socket_path();
+    let no_instance = |e: std::io::Error| {
+        Failure::NoInstance(format!(
+            "Can't reach ytermusic on `{}`: {e}",
+            path.display()
+        ))
+    };
+    let mut stream = UnixStream::connect(&path).map_err(no_instance)?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(no_instance)?;
+    let mut json = serde_json::to_string(command).unwrap();
+    json.push('\n');
+    stream.write_all(json.as_bytes()).map_err(no_instance)?;
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(no_instance)?;
+    match serde_json::from_str(&line) {
+        Ok(Response::Error { message }) => Err(Failure::Refused(message)),
+        Ok(response) => Ok(response),
+        Err(e) => Err(Failure::NoInstance(format!("Invalid response: {e}"))),
+    }
+}
+
+#[cfg(not(unix))]
+fn request(_: &Command) -> Result<Response, Failure> {
+    Err(Failure::NoInstance(
+        "The control socket is only available on Unix".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ytapi2::types::YoutubeMusicVideoRef;
+
+    use super::*;
+
+    fn status() -> Status {
+        Status {
+            video: Some(YoutubeMusicVideoRef {
+                title: "Song {artist}".to_string(),
+                author: "Artist".to_string(),
+                album: "Album".to_string(),
+                video_id: "dQw4w9WgXcQ".to_string(),
+                duration: "3:25".to_string(),
+            }),
+            paused: true,
+            loading: false,
+            position: 65.9,
+            duration: Some(3725.0),
+            volume: 80,
+            repeat: "all".to_string(),
+            shuffle: false,
+            index: Some(0),
+            queue_length: 1,
+        }
+    }
+
+    #[test]
+    fn formats_placeholders_once() {
+        assert_eq!(
+            format_status(DEFAULT_FORMAT, &status()),
+            "Artist - Song {artist}"
+        );
+        assert_eq!(
+            format_status(
+                "[{state}] {position}/{duration} {volume}% {repeat} {shuffle}",
+                &status()
+            ),
+            "[paused] 1:05/1:02:05 80% all off"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders() {
+        assert_eq!(format_status("{foo} {album}", &status()), "{foo} Album");
+        assert_eq!(format_status("{{title}}", &status()), "{Song {artist}}");
+        assert_eq!(format_status("{title", &status()), "{title");
+        assert_eq!(format_status("{album} {", &status()), "Album {");
+    }
+
+    #[test]
+    fn formats_stopped_status() {
+        let status = Status {
+            video: None,
+            duration: None,
+            ..status()
+        };
+        assert_eq!(format_status("{title}|{duration}", &status), "|");
+    }
+
+    #[test]
+    fn formats_time() {
+        assert_eq!(format_time(0.0), "0:00");
+        assert_eq!(format_time(59.9), "0:59");
+        assert_eq!(format_time(754.0), "12:34");
+        assert_eq!(format_time(3599.0), "59:59");
+        assert_eq!(format_time(3600.0), "1:00:00");
+        assert_eq!(format_time(36125.0), "10:02:05");
+    }
+
+    #[test]
+    fn parses_offsets() {
+        let parse = |x| parse_offset::<f64>(x).ok();
+        assert_eq!(parse("+5"), Some((5.0, true)));
+        assert_eq!(parse("-5"), Some((-5.0, true)));
+        assert_eq!(parse("5"), Some((5.0, false)));
+        assert_eq!(parse("2.5"), Some((2.5, false)));
+        let parse = |x| parse_offset::<i32>(x).ok();
+        assert_eq!(parse("+5"), Some((5, true)));
+        assert_eq!(parse("-5"), Some((-5, true)));
+        assert_eq!(parse("5"), Some((5, false)));
+        assert_eq!(parse("2.5"), None);
+        assert!(matches!(parse_offset::<i32>("x"), Err(Failure::Usage(_))));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let failures = [
+            Failure::Refused(String::new()),
+            Failure::Usage(String::new()),
+            Failure::NoInstance(String::new()),
+            Failure::Stopped,
+        ];
+        assert_eq!(failures.map(|x| x.code()), [1, 2, 3, 4]);
+    }
+}
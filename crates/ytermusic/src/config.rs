@@ -0,0 +1,690 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::ErrorKind,
+    net::SocketAddr,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use log::LevelFilter;
+use once_cell::sync::OnceCell;
+use ratatui::style::Color;
+use serde::{Deserialize, de::DeserializeOwned};
+use toml::Spanned;
+
+use crate::{
+    structures::queue::RepeatMode,
+    systems::{
+        loudness::{Normalization, NormalizationMode},
+        player::MAX_CROSSFADE,
+    },
+    term::{
+        keymap::{Action, KeySequence, Keymap},
+        theme::{Panel, Theme},
+    },
+    utils::get_project_dirs,
+};
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+/// More parallel downloads get throttled by YouTube
+const MAX_CONCURRENCY: u64 = 16;
+/// Loudness targets accepted for the normalization, in LUFS
+const TARGET_LUFS: RangeInclusive<f64> = -30.0..=-5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioQuality {
+    /// The stream with the highest bitrate
+    High,
+    /// The stream with the lowest bitrate, for slow or metered connections
+    Low,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Replaces the cache folder of the platform
+    pub cache_dir: Option<PathBuf>,
+    /// `None` keeps the level given by `YTMUSIC_LOG`
+    pub log_level: Option<LevelFilter>,
+    pub player: PlayerConfig,
+    pub download: DownloadConfig,
+    /// The preset with the bindings of the file
+    pub keymap: Keymap,
+    pub ui: UiConfig,
+    pub mpd: MpdConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerConfig {
+    pub seek_step: Duration,
+    /// Percentage added or removed by the volume keys
+    pub volume_step: u8,
+    /// Repeat mode used when no session is restored
+    pub repeat: RepeatMode,
+    /// Zero plays the tracks back to back
+    pub crossfade: Duration,
+    pub normalization: Normalization,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UiConfig {
+    pub theme: Theme,
+    pub hidden: Vec<Panel>,
+}
+
+impl UiConfig {
+    pub fn shows(&self, panel: Panel) -> bool {
+        !self.hidden.contains(&panel)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadConfig {
+    /// Number of videos downloaded at the same time
+    pub concurrency: usize,
+    pub quality: AudioQuality,
+}
+
+/// The server for the MPD clients, it has no password so it listens on localhost by default
+#[derive(Debug, Clone)]
+pub struct MpdConfig {
+    pub enabled: bool,
+    pub address: SocketAddr,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            log_level: None,
+            player: PlayerConfig {
+                seek_step: Duration::from_secs(5),
+                volume_step: 5,
+                repeat: RepeatMode::Off,
+                crossfade: Duration::ZERO,
+                normalization: Normalization::default(),
+            },
+            download: DownloadConfig {
+                concurrency: 4,
+                quality: AudioQuality::High,
+            },
+            keymap: Keymap::default(),
+            ui: UiConfig::default(),
+            mpd: MpdConfig {
+                enabled: false,
+                address: SocketAddr::from(([127, 0, 0, 1], 6600)),
+            },
+        }
+    }
+}
+
+/// The configuration read at startup, the default one when there is no file
+pub fn get_config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn get_config_file_path() -> PathBuf {
+    get_project_dirs()
+        .map(|x| x.config_dir().to_owned())
+        .unwrap_or_default()
+        .join("config.toml")
+}
+
+/// Theme files are `<name>.toml` files in this folder
+pub fn get_themes_dir() -> PathBuf {
+    get_config_file_path().with_file_name("themes")
+}
+
+/// Reads `config.toml`, a missing file gives the default configuration
+pub fn load_config() -> Result<(), Vec<ConfigError>> {
+    let config = match std::fs::read_to_string(get_config_file_path()) {
+        Ok(content) => parse_config(&content)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => Config::default(),
+        Err(e) => {
+            return Err(vec![ConfigError {
+                file: None,
+                line: None,
+                message: format!("Can't read the file: {e}"),
+            }]);
+        }
+    };
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    /// Theme file of the error, `None` for `config.toml`
+    pub file: Option<PathBuf>,
+    /// Line of the value, starting at 1
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "`{}` ", file.display())?;
+        }
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    cache_dir: Option<Spanned<PathBuf>>,
+    log_level: Option<Spanned<String>>,
+    #[serde(default)]
+    player: RawPlayerConfig,
+    #[serde(default)]
+    download: RawDownloadConfig,
+    #[serde(default)]
+    keymap: RawKeymapConfig,
+    #[serde(default)]
+    ui: RawUiConfig,
+    #[serde(default)]
+    mpd: RawMpdConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawUiConfig {
+    /// A built-in palette or a theme file
+    theme: Option<Spanned<String>>,
+    #[serde(default)]
+    hide: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawPlayerConfig {
+    /// In seconds
+    seek_step: Option<Spanned<u64>>,
+    volume_step: Option<Spanned<u64>>,
+    repeat: Option<Spanned<String>>,
+    /// In seconds
+    crossfade: Option<Spanned<u64>>,
+    normalization: Option<Spanned<String>>,
+    target_lufs: Option<Spanned<f64>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawDownloadConfig {
+    concurrency: Option<Spanned<u64>>,
+    quality: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawMpdConfig {
+    enabled: Option<bool>,
+    address: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawKeymapConfig {
+    preset: Option<Spanned<String>>,
+    /// Bindings replacing the ones of the preset, by action name
+    #[serde(default)]
+    bindings: BTreeMap<String, Spanned<RawBindings>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBindings {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Collects the invalid values with their line
+struct Validator<'a> {
+    content: &'a str,
+    /// `None` for `config.toml`
+    file: Option<&'a Path>,
+    errors: Vec<ConfigError>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(content: &'a str, file: Option<&'a Path>) -> Self {
+        Self {
+            content,
+            file,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Reads the file, only the syntax and the types are checked
+    fn deserialize<T: DeserializeOwned>(&self) -> Result<T, Vec<ConfigError>> {
+        toml::from_str(self.content).map_err(|e| {
+            vec![ConfigError {
+                file: self.file.map(Path::to_owned),
+                line: e.span().map(|x| self.line_of(x.start)),
+                message: e.message().to_string(),
+            }]
+        })
+    }
+
+    /// Gives the value when no error was found
+    fn finish<T>(mut self, value: T) -> Result<T, Vec<ConfigError>> {
+        if self.errors.is_empty() {
+            Ok(value)
+        } else {
+            self.errors.sort_by_key(|x| (x.file.clone(), x.line));
+            Err(self.errors)
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.content[..offset.min(self.content.len())]
+            .matches('\n')
+            .count()
+            + 1
+    }
+
+    fn error<T>(&mut self, value: &Spanned<T>, message: String) {
+        self.errors.push(ConfigError {
+            file: self.file.map(Path::to_owned),
+            line: Some(self.line_of(value.span().start)),
+            message,
+        });
+    }
+
+    /// `expected` lists the accepted values in the error
+    fn parse<T>(
+        &mut self,
+        value: Option<Spanned<String>>,
+        parse: impl Fn(&str) -> Option<T>,
+        expected: &str,
+    ) -> Option<T> {
+        let value = value?;
+        let parsed = parse(value.get_ref());
+        if parsed.is_none() {
+            self.error(
+                &value,
+                format!("`{}` is invalid, expected {expected}", value.get_ref()),
+            );
+        }
+        parsed
+    }
+
+    fn range<T: PartialOrd + Display>(
+        &mut self,
+        value: Option<Spanned<T>>,
+        name: &str,
+        range: RangeInclusive<T>,
+    ) -> Option<T> {
+        let value = value?;
+        if !range.contains(value.get_ref()) {
+            self.error(
+                &value,
+                format!(
+                    "`{name}` must be between {} and {}",
+                    range.start(),
+                    range.end()
+                ),
+            );
+            return None;
+        }
+        Some(value.into_inner())
+    }
+}
+
+/// Checks the values of the file, every invalid value is reported
+fn parse_config(content: &str) -> Result<Config, Vec<ConfigError>> {
+    let mut validator = Validator::new(content, None);
+    let raw = validator.deserialize::<RawConfig>()?;
+
+    if let Some(cache_dir) = &raw.cache_dir
+        && cache_dir.get_ref().as_os_str().is_empty()
+    {
+        validator.error(cache_dir, "`cache_dir` can't be empty".to_string());
+    }
+    let mut config = Config {
+        cache_dir: raw.cache_dir.map(Spanned::into_inner),
+        log_level: validator.parse(
+            raw.log_level,
+            |x| LevelFilter::from_str(x).ok(),
+            "off, error, warn, info, debug or trace",
+        ),
+        ..Config::default()
+    };
+    if let Some(repeat) = validator.parse(raw.player.repeat, parse_repeat, "off, one or all") {
+        config.player.repeat = repeat;
+    }
+    if let Some(quality) = validator.parse(raw.download.quality, parse_quality, "high or low") {
+        config.download.quality = quality;
+    }
+    if let Some(seek_step) = validator.range(raw.player.seek_step, "seek_step", 1..=600) {
+        config.player.seek_step = Duration::from_secs(seek_step);
+    }
+    if let Some(volume_step) = validator.range(raw.player.volume_step, "volume_step", 1..=100) {
+        config.player.volume_step = volume_step as u8;
+    }
+    if let Some(crossfade) = validator.range(
+        raw.player.crossfade,
+        "crossfade",
+        0..=MAX_CROSSFADE.as_secs(),
+    ) {
+        config.player.crossfade = Duration::from_secs(crossfade);
+    }
+    if let Some(mode) = validator.parse(
+        raw.player.normalization,
+        parse_normalization,
+        "off, track or album",
+    ) {
+        config.player.normalization.mode = mode;
+    }
+    if let Some(target_lufs) = validator.range(raw.player.target_lufs, "target_lufs", TARGET_LUFS) {
+        config.player.normalization.target_lufs = target_lufs;
+    }
+    if let Some(concurrency) =
+        validator.range(raw.download.concurrency, "concurrency", 1..=MAX_CONCURRENCY)
+    {
+        config.download.concurrency = concurrency as usize;
+    }
+    if let Some(keymap) = validator.parse(
+        raw.keymap.preset,
+        Keymap::preset,
+        &Keymap::PRESETS.join(" or "),
+    ) {
+        config.keymap = keymap;
+    }
+    parse_bindings(&mut validator, &mut config.keymap, raw.keymap.bindings);
+    if let Some(theme) = raw.ui.theme.and_then(|x| load_theme(&mut validator, x)) {
+        config.ui.theme = theme;
+    }
+    for panel in raw.ui.hide {
+        if let Some(panel) = validator.parse(
+            Some(panel),
+            Panel::from_name,
+            "queue, progress, status or hints",
+        ) {
+            config.ui.hidden.push(panel);
+        }
+    }
+    if let Some(enabled) = raw.mpd.enabled {
+        config.mpd.enabled = enabled;
+    }
+    if let Some(address) = validator.parse(
+        raw.mpd.address,
+        |x| SocketAddr::from_str(x).ok(),
+        "an address such as 127.0.0.1:6600",
+    ) {
+        config.mpd.address = address;
+    }
+
+    validator.finish(config)
+}
+
+/// A theme file takes precedence over the built-in palette with the same name
+fn load_theme(validator: &mut Validator, name: Spanned<String>) -> Option<Theme> {
+    let path = get_themes_dir().join(format!("{}.toml", name.get_ref()));
+    match std::fs::read_to_string(&path) {
+        Ok(content) => match parse_theme(&content, &path) {
+            Ok(theme) => Some(theme),
+            Err(errors) => {
+                validator.errors.extend(errors);
+                None
+            }
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => validator.parse(
+            Some(name),
+            Theme::preset,
+            &format!(
+                "one of {} or a file in `{}`",
+                Theme::PRESETS.join(", "),
+                get_themes_dir().display()
+            ),
+        ),
+        Err(e) => {
+            validator.error(&name, format!("Can't read `{}`: {e}", path.display()));
+            None
+        }
+    }
+}
+
+/// Theme files set the colors of a `base` palette, `dark` by default
+fn parse_theme(content: &str, path: &Path) -> Result<Theme, Vec<ConfigError>> {
+    let mut validator = Validator::new(content, Some(path));
+    let mut raw = validator.deserialize::<BTreeMap<String, Spanned<String>>>()?;
+    let mut theme = validator
+        .parse(
+            raw.remove("base"),
+            Theme::preset,
+            &format!("one of {}", Theme::PRESETS.join(", ")),
+        )
+        .unwrap_or_default();
+    for (name, value) in raw {
+        match theme.colors_mut().into_iter().find(|(x, _)| *x == name) {
+            Some((_, color)) => match Color::from_str(value.get_ref()) {
+                Ok(parsed) => *color = parsed,
+                Err(_) => validator.error(
+                    &value,
+                    format!(
+                        "`{}` isn't a color, expected a name, #rrggbb or a number up to 255",
+                        value.get_ref()
+                    ),
+                ),
+            },
+            None => validator.error(&value, format!("`{name}` isn't a color of the themes")),
+        }
+    }
+    validator.finish(theme)
+}
+
+/// Replaces the bindings of the preset, a key bound to two actions is an error
+fn parse_bindings(
+    validator: &mut Validator,
+    keymap: &mut Keymap,
+    bindings: BTreeMap<String, Spanned<RawBindings>>,
+) {
+    let mut actions = Vec::new();
+    for (name, value) in bindings {
+        match Action::from_name(&name) {
+            Some(action) => {
+                // All the overridden actions are unbound first, so they can swap their keys
+                keymap.unbind(action);
+                actions.push((action, value));
+            }
+            None => validator.error(&value, format!("`{name}` isn't an action")),
+        }
+    }
+    for (action, value) in actions {
+        let sequences = match value.get_ref() {
+            RawBindings::One(x) => vec![x.as_str()],
+            RawBindings::Many(x) => x.iter().map(String::as_str).collect(),
+        };
+        let result = sequences
+            .into_iter()
+            .map(KeySequence::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|x| keymap.bind(action, x));
+        if let Err(message) = result {
+            validator.error(&value, message);
+        }
+    }
+}
+
+fn parse_repeat(value: &str) -> Option<RepeatMode> {
+    match value {
+        "off" => Some(RepeatMode::Off),
+        "one" => Some(RepeatMode::One),
+        "all" => Some(RepeatMode::All),
+        _ => None,
+    }
+}
+
+fn parse_normalization(value: &str) -> Option<NormalizationMode> {
+    match value {
+        "off" => Some(NormalizationMode::Off),
+        "track" => Some(NormalizationMode::Track),
+        "album" => Some(NormalizationMode::Album),
+        _ => None,
+    }
+}
+
+fn parse_quality(value: &str) -> Option<AudioQuality> {
+    match value {
+        "high" => Some(AudioQuality::High),
+        "low" => Some(AudioQuality::Low),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line and the message of each error
+    fn errors(content: &str) -> Vec<(Option<usize>, String)> {
+        let errors = parse_config(content).err().unwrap();
+        errors.into_iter().map(|x| (x.line, x.message)).collect()
+    }
+
+    #[test]
+    fn empty_file_is_default() {
+        let config = parse_config("").ok().unwrap();
+        let default = Config::default();
+        assert_eq!(config.player.seek_step, default.player.seek_step);
+        assert_eq!(config.player.crossfade, Duration::ZERO);
+        assert_eq!(config.keymap.name(), "default");
+        assert!(!config.mpd.enabled);
+        assert_eq!(config.mpd.address, default.mpd.address);
+    }
+
+    #[test]
+    fn reads_every_value() {
+        let config = parse_config(
+            r#"
+log_level = "debug"
+cache_dir = "/tmp/ytermusic"
+
+[player]
+seek_step = 10
+volume_step = 2
+repeat = "all"
+crossfade = 12
+normalization = "album"
+target_lufs = -18.0
+
+[download]
+concurrency = 8
+quality = "low"
+
+[keymap]
+preset = "vim"
+
+[keymap.bindings]
+quit = ["ctrl+q", "Z Z"]
+
+[ui]
+theme = "light"
+hide = ["hints", "status"]
+
+[mpd]
+enabled = true
+address = "0.0.0.0:6601"
+"#,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(config.log_level, Some(LevelFilter::Debug));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/tmp/ytermusic")));
+        assert_eq!(config.player.seek_step, Duration::from_secs(10));
+        assert_eq!(config.player.volume_step, 2);
+        assert_eq!(config.player.repeat, RepeatMode::All);
+        assert_eq!(config.player.crossfade, Duration::from_secs(12));
+        assert_eq!(
+            config.player.normalization,
+            Normalization {
+                mode: NormalizationMode::Album,
+                target_lufs: -18.0,
+            }
+        );
+        assert_eq!(config.download.concurrency, 8);
+        assert_eq!(config.download.quality, AudioQuality::Low);
+        assert_eq!(config.keymap.name(), "vim");
+        let quit = config.keymap.get_bindings(Action::Quit);
+        let quit = quit.map(KeySequence::to_string).collect::<Vec<_>>();
+        assert_eq!(quit, ["ctrl+q", "Z Z"]);
+        let light = Theme::preset("light").unwrap();
+        assert_eq!(config.ui.theme.highlight, light.highlight);
+        assert_eq!(config.ui.hidden, [Panel::Hints, Panel::Status]);
+        assert!(config.mpd.enabled);
+        assert_eq!(config.mpd.address, SocketAddr::from(([0, 0, 0, 0], 6601)));
+    }
+
+    #[test]
+    fn reports_every_invalid_value() {
+        let themes = format!(
+            "one of {} or a file in `{}`",
+            Theme::PRESETS.join(", "),
+            get_themes_dir().display()
+        );
+        assert_eq!(
+            errors(
+                r#"
+[player]
+crossfade = 20
+seek_step = 10
+target_lufs = 0.0
+
+[ui]
+theme = "no-such-theme"
+
+[mpd]
+address = "localhost"
+"#
+            ),
+            [
+                (Some(3), "`crossfade` must be between 0 and 12".to_string()),
+                (
+                    Some(5),
+                    "`target_lufs` must be between -30 and -5".to_string()
+                ),
+                (
+                    Some(8),
+                    format!("`no-such-theme` is invalid, expected {themes}")
+                ),
+                (
+                    Some(11),
+                    "`localhost` is invalid, expected an address such as 127.0.0.1:6600"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_bindings() {
+        assert_eq!(
+            errors("[keymap.bindings]\nsearch = \"s\"\nfoo = \"x\"\n"),
+            [
+                (
+                    Some(2),
+                    "`s` of search conflicts with `s` of shuffle".to_string()
+                ),
+                (Some(3), "`foo` isn't an action".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let errors = errors("[player]\nvolume_step = \"loud\"\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Some(2));
+        let errors = self::errors("colour = \"red\"\n");
+        assert_eq!(errors[0].0, Some(1));
+        assert!(errors[0].1.contains("colour"), "{}", errors[0].1);
+    }
+}
@@ -3,12 +3,16 @@ use std::path::PathBuf;
 use log::warn;
 use once_cell::sync::Lazy;
 
-use crate::utils::get_project_dirs;
+use crate::{config::get_config, utils::get_project_dirs};
 
 pub const INTRODUCTION: &str = r#"Usage: ytermusic [options]
 
 YTerMusic is a TUI based Youtube Music Player that aims to be as fast and simple as possible.
 In order to get your music, create a file "headers.txt" in the config folder, and copy the Cookie and User-Agent from request header of the music.youtube.com html document "/" page.
+The settings are read from "config.toml" in the same folder, see --files for its location.
+The shortcuts below can be changed in its [keymap] section, with the "default" or "vim" preset.
+The colors are set in its [ui] section, with a built-in theme or a file of the "themes" folder.
+MPD clients can drive the player once "enabled = true" is set in its [mpd] section, on localhost:6600 by default.
 More info at: https://github.com/Drack112/Youtube-Music-Cli
 
 Options:
@@ -16,27 +20,20 @@ Options:
         --files             Show the location of the ytermusic files
         --fix-db            Fix the database in cache
         --clear-cache       Erase all the files in cache
+        --daemon            Play without a terminal, controlled through a socket (Unix only)
+
+A running instance, with or without a terminal, is controlled with "ytermusic-ctl", see "ytermusic-ctl --help".
 
-Shortcuts:
-        Use your mouse to click in lists if your terminal has mouse support
-        Space                     play/pause
-        Enter                     select a playlist or a music
-        f                         search
-        s                         shuffle
-        r                         remove a music from the main playlist
-        Arrow Right or >          skip 5 seconds
-        Arrow Left or <           go back 5 seconds
-        CTRL + Arrow Right (>)    go to the next song
-        CTRL + Arrow Left  (<)    go to the previous song
-        +                         volume up
-        -                         volume down
-        Arrow down                scroll down
-        Arrow up                  scroll up
-        ESC                       exit the current menu
-        CTRL + C or CTRL + D      quit
 "#;
 
+/// Sent when the cookies come from a browser, as there is no `headers.txt` to read it from
+pub const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:108.0) Gecko/20100101 Firefox/108.0";
+
 pub static CACHE_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    if let Some(dir) = &get_config().cache_dir {
+        return dir.clone();
+    }
     let pdir = get_project_dirs();
     if let Some(dir) = pdir {
         return dir.cache_dir().to_path_buf();
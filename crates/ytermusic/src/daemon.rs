@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use log::{error, info, warn};
+use tokio::{
+    select,
+    signal::unix::{SignalKind, signal},
+};
+
+use crate::{
+    errors::ErrorCategory,
+    run_service,
+    shutdown::{guard_shutdown, is_shutdown_sent, shutdown},
+    spawn_connection,
+    systems::{
+        control::{Controller, Request},
+        download::spawn_download_system,
+        player::PlayerAction,
+    },
+    term::ManagerMessage,
+};
+
+/// Delay before connecting again after a network error, doubled after each failure
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+/// Drives the player without a terminal, from the commands of the control socket
+pub struct Daemon {
+    updater: Sender<ManagerMessage>,
+    controller: Controller,
+    connected: bool,
+    /// Nobody can pick the retry action of the error screen, the daemon retries by itself
+    reconnect_delay: Duration,
+}
+
+impl Daemon {
+    pub fn new(player: Sender<PlayerAction>, updater: Sender<ManagerMessage>) -> Self {
+        Self {
+            updater,
+            controller: Controller::new(player),
+            connected: false,
+            reconnect_delay: RECONNECT_DELAY,
+        }
+    }
+
+    /// Runs until the shutdown
+    pub async fn run(mut self, updater: Receiver<ManagerMessage>, requests: Receiver<Request>) {
+        let guard = guard_shutdown();
+        spawn_signal_handler();
+        while !is_shutdown_sent() {
+            select! {
+                Ok(message) = updater.recv_async() => self.handle_manager_message(message),
+                Ok((command, response)) = requests.recv_async() => {
+                    self.controller.handle_command(command, response);
+                }
+                else => break,
+            }
+        }
+        info!("Daemon stopped");
+        drop(guard);
+    }
+
+    fn handle_manager_message(&mut self, message: ManagerMessage) {
+        self.controller.observe(&message);
+        match message {
+            // The errors are sent to the error screen
+            ManagerMessage::PassTo(_, message) => self.handle_manager_message(*message),
+            ManagerMessage::Connect => spawn_connection(self.updater.clone()),
+            ManagerMessage::Connected(instance) => {
+                // The download workers keep the first instance, as in the terminal interface
+                if !self.connected {
+                    spawn_download_system(instance, self.updater.clone());
+                }
+                self.connected = true;
+                self.reconnect_delay = RECONNECT_DELAY;
+            }
+            ManagerMessage::Error(ErrorCategory::Network, message, _) if !self.connected => {
+                let delay = self.reconnect_delay;
+                error!(
+                    "{}: {message}, connecting again in {}s",
+                    ErrorCategory::Network.name(),
+                    delay.as_secs()
+                );
+                self.reconnect_delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                let updater = self.updater.clone();
+                run_service(async move {
+                    tokio::time::sleep(delay).await;
+                    updater.send(ManagerMessage::Connect).unwrap();
+                });
+            }
+            ManagerMessage::Error(category, message, _) => {
+                error!("{}: {message}", category.name());
+            }
+            ManagerMessage::Quit => shutdown(),
+            // The other messages are for the screens
+            _ => (),
+        }
+    }
+}
+
+/// Closing the terminal doesn't stop the daemon, `SIGTERM` stops it cleanly
+fn spawn_signal_handler() {
+    run_service(async {
+        let (Ok(mut hangup), Ok(mut terminate)) = (
+            signal(SignalKind::hangup()),
+            signal(SignalKind::terminate()),
+        ) else {
+            warn!("Can't listen to the signals");
+            return;
+        };
+        loop {
+            select! {
+                _ = hangup.recv() => info!("Hangup ignored"),
+                _ = terminate.recv() => {
+                    info!("SIGTERM received");
+                    shutdown();
+                }
+            }
+        }
+    });
+}
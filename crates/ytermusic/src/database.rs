@@ -1,6 +1,27 @@
 use database::YTLocalDatabase;
 use once_cell::sync::Lazy;
+use ytapi2::types::YoutubeMusicVideoRef;
 
 use crate::consts::CACHE_DIR;
 
 pub static DATABASE: Lazy<YTLocalDatabase> = Lazy::new(|| YTLocalDatabase::new(CACHE_DIR.clone()));
+
+/// The local musics whose title, author or album contain every word of the query
+pub fn search_local(query: &str) -> Vec<YoutubeMusicVideoRef> {
+    let words = query
+        .to_lowercase()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    DATABASE
+        .videos()
+        .into_iter()
+        .filter(|video| {
+            let text = format!("{} {} {}", video.title, video.author, video.album).to_lowercase();
+            words.iter().all(|x| text.contains(x))
+        })
+        .collect()
+}
@@ -1,27 +1,154 @@
+use std::io::ErrorKind;
+
 use flume::Sender;
+use ytapi2::types::{PlayabilityStatus, YoutubeMusicError};
 
 use crate::term::{ManagerMessage, Screens};
 
+/// Kind of failure, it decides what the error screen suggests to fix it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    AudioDevice,
+    Network,
+    /// The cookies are missing, invalid or expired
+    Auth,
+    Disk,
+    Other,
+}
+
+impl ErrorCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCategory::AudioDevice => "Audio device",
+            ErrorCategory::Network => "Network",
+            ErrorCategory::Auth => "Authentication",
+            ErrorCategory::Disk => "Disk",
+            ErrorCategory::Other => "Error",
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorCategory::AudioDevice => {
+                "No sound can be played. Check that an output device is plugged in and not held by another program."
+            }
+            ErrorCategory::Network => {
+                "YouTube Music can't be reached. Check the internet connection."
+            }
+            ErrorCategory::Auth => {
+                "The cookies are invalid or expired. Copy them again in `headers.txt` (see `ytermusic --files`)."
+            }
+            ErrorCategory::Disk => {
+                "The cache can't be written. Check the free space and the permissions of the cache folder."
+            }
+            ErrorCategory::Other => {
+                "An unexpected error occurred, more details are in the log file."
+            }
+        }
+    }
+
+    /// Label of the action sent with the errors of this category
+    pub fn action_name(self) -> Option<&'static str> {
+        match self {
+            ErrorCategory::AudioDevice => Some("Reopen the audio device"),
+            ErrorCategory::Network => Some("Retry"),
+            ErrorCategory::Auth => Some("Read headers.txt again"),
+            ErrorCategory::Disk | ErrorCategory::Other => None,
+        }
+    }
+
+    /// Message dispatched when the user picks the action of the category
+    pub fn action(self) -> Option<ManagerMessage> {
+        match self {
+            ErrorCategory::AudioDevice => Some(ManagerMessage::RestartPlayer),
+            // Connecting again also reads the cookies again
+            ErrorCategory::Network | ErrorCategory::Auth => Some(ManagerMessage::Connect),
+            ErrorCategory::Disk | ErrorCategory::Other => None,
+        }
+    }
+}
+
+pub trait Categorize {
+    fn category(&self) -> ErrorCategory;
+}
+
+impl Categorize for YoutubeMusicError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            YoutubeMusicError::RequestError(_) => ErrorCategory::Network,
+            YoutubeMusicError::NoCookieAttribute
+            | YoutubeMusicError::NoSapsidInCookie
+            | YoutubeMusicError::InvalidCookie(_)
+            | YoutubeMusicError::NeedToLogin
+            | YoutubeMusicError::InvalidHeaders
+            | YoutubeMusicError::NotPlayable(PlayabilityStatus::LoginRequired(_)) => {
+                ErrorCategory::Auth
+            }
+            // YouTube Music sends a consent or login page instead of the app to bad cookies
+            YoutubeMusicError::CantFindInnerTubeApiKey(_)
+            | YoutubeMusicError::CantFindInnerTubeClientVersion(_)
+            | YoutubeMusicError::CantFindVisitorData(_) => ErrorCategory::Auth,
+            // The only file read by the client is `headers.txt`
+            YoutubeMusicError::IoError(e) if e.kind() == ErrorKind::NotFound => ErrorCategory::Auth,
+            YoutubeMusicError::IoError(e) => e.category(),
+            _ => ErrorCategory::Other,
+        }
+    }
+}
+
+impl Categorize for std::io::Error {
+    fn category(&self) -> ErrorCategory {
+        match self.kind() {
+            ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::TimedOut
+            | ErrorKind::HostUnreachable
+            | ErrorKind::NetworkUnreachable => ErrorCategory::Network,
+            _ => ErrorCategory::Disk,
+        }
+    }
+}
+
+impl Categorize for rodio::StreamError {
+    fn category(&self) -> ErrorCategory {
+        ErrorCategory::AudioDevice
+    }
+}
+
+impl Categorize for String {
+    fn category(&self) -> ErrorCategory {
+        ErrorCategory::Other
+    }
+}
+
+/// Shows an error on the error screen with the action of its category
+pub fn report_error(updater: &Sender<ManagerMessage>, category: ErrorCategory, message: String) {
+    updater
+        .send(ManagerMessage::PassTo(
+            Screens::DeviceLost,
+            Box::new(ManagerMessage::Error(
+                category,
+                message,
+                Box::new(category.action()),
+            )),
+        ))
+        .unwrap();
+}
+
 pub fn handle_error_option<T, E>(
     updater: &Sender<ManagerMessage>,
     error_type: &'static str,
     a: Result<E, T>,
 ) -> Option<E>
 where
-    T: std::fmt::Debug,
+    T: std::fmt::Debug + Categorize,
 {
     match a {
         Ok(e) => Some(e),
         Err(a) => {
-            updater
-                .send(ManagerMessage::PassTo(
-                    Screens::DeviceLost,
-                    Box::new(ManagerMessage::Error(
-                        format!("{error_type} {a:?}"),
-                        Box::new(None),
-                    )),
-                ))
-                .unwrap();
+            report_error(updater, a.category(), format!("{error_type} {a:?}"));
             None
         }
     }
@@ -29,7 +156,7 @@ where
 
 pub fn handle_error<T>(updater: &Sender<ManagerMessage>, error_type: &'static str, a: Result<(), T>)
 where
-    T: std::fmt::Debug,
+    T: std::fmt::Debug + Categorize,
 {
     let _ = handle_error_option(updater, error_type, a);
 }
@@ -2,27 +2,42 @@ use std::{
     panic,
     path::{Path, PathBuf},
     str::FromStr,
-    sync::RwLock,
+    sync::{Arc, RwLock},
 };
 
 use flume::{Receiver, Sender};
 use log::{error, info};
 use once_cell::sync::Lazy;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, USER_AGENT};
 use tokio::select;
+use ytapi2::{
+    instance::YoutubeMusicInstance,
+    types::{Result as YoutubeMusicResult, YoutubeMusicError},
+};
 
 use crate::{
-    consts::{CACHE_DIR, HEADER_TUTORIAL, INTRODUCTION},
+    config::{get_config, get_config_file_path, get_themes_dir, load_config},
+    consts::{CACHE_DIR, DEFAULT_USER_AGENT, HEADER_TUTORIAL, INTRODUCTION},
     database::DATABASE,
+    errors::{ErrorCategory, handle_error_option, report_error},
+    protocol::get_socket_path,
     shutdown::{ShutdownSignal, shutdown},
-    structures::{media::run_window_handler, perfomance::STARTUP_TIME},
-    systems::logger::{get_log_file_path, init},
-    term::ManagerMessage,
+    structures::{media::run_window_handler, perfomance::STARTUP_TIME, session::Session},
+    systems::{
+        logger::{get_log_file_path, init},
+        player::{PlayerAction, spawn_player_system},
+    },
+    term::{Manager, ManagerMessage},
     utils::get_project_dirs,
 };
 
+mod config;
 mod consts;
+#[cfg(unix)]
+mod daemon;
 mod database;
 mod errors;
+mod protocol;
 mod shutdown;
 mod structures;
 mod systems;
@@ -130,15 +145,34 @@ fn get_header_file() -> Result<(String, PathBuf), (std::io::Error, PathBuf)> {
 }
 
 fn main() {
-    if let Some(arg) = std::env::args().nth(1) {
+    let arg = std::env::args().nth(1);
+    let mut daemon = false;
+    if let Err(errors) = load_config() {
+        println!(
+            "Invalid configuration in `{}`:",
+            get_config_file_path().display()
+        );
+        for error in errors {
+            println!(" - {error}");
+        }
+        // The help and the location of the files stay available to fix it
+        if !matches!(arg.as_deref(), Some("-h" | "--help" | "--files")) {
+            return;
+        }
+    }
+
+    if let Some(arg) = arg {
         match arg.as_str() {
             "-h" | "--help" => {
-                println!("{}", INTRODUCTION);
+                println!("{}{}", INTRODUCTION, get_config().keymap.help_text());
                 return;
             }
             "--files" => {
                 println!("# Location of ytermusic files");
+                println!(" - Config: {}", get_config_file_path().display());
+                println!(" - Themes: {}", get_themes_dir().display());
                 println!(" - Log: {}", get_log_file_path().display());
+                println!(" - Socket: {}", get_socket_path().display());
                 println!(" - Headers: {}", get_header_file().unwrap().1.display());
                 return;
             }
@@ -159,6 +193,12 @@ fn main() {
                 }
                 return;
             }
+            #[cfg(unix)]
+            "--daemon" => {
+                std::fs::write(get_log_file_path(), "# YTerMusic log file\n\n").unwrap();
+                init().expect("Failed to initialize logger");
+                daemon = true;
+            }
             "--with-auto-cookies" => {
                 std::fs::write(get_log_file_path(), "# YTerMusic log file\n\n").unwrap();
                 init().expect("Failed to initialize logger");
@@ -180,6 +220,8 @@ fn main() {
                 println!("Unknown argument `{e}`");
                 println!("Here are the available arguments:");
                 println!(" - --files: Show the location of the ytermusic files");
+                #[cfg(unix)]
+                println!(" - --daemon: Play without a terminal, controlled through a socket");
                 println!(" - --clear-cache: Erase all the files in cache");
                 println!(" - --fix-db: Fix the database");
                 return;
@@ -196,7 +238,7 @@ fn main() {
         shutdown();
     }));
 
-    app_start();
+    app_start(daemon);
 }
 
 pub fn try_get_cookies() -> Option<String> {
@@ -204,7 +246,40 @@ pub fn try_get_cookies() -> Option<String> {
     cookies.clone()
 }
 
-async fn app_start_main(updater_r: Receiver<ManagerMessage>, updater_s: Sender<ManagerMessage>) {
+async fn connect() -> YoutubeMusicResult<YoutubeMusicInstance> {
+    if let Some(cookies) = try_get_cookies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            cookies
+                .parse()
+                .map_err(|_| YoutubeMusicError::InvalidHeaders)?,
+        );
+        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+        return YoutubeMusicInstance::new(headers, None).await;
+    }
+    let (_, path) = get_header_file().map_err(|(e, _)| YoutubeMusicError::IoError(e))?;
+    YoutubeMusicInstance::from_header_file(&path).await
+}
+
+/// Connects in the background, `Connected` is sent on success and an error otherwise
+fn spawn_connection(updater: Sender<ManagerMessage>) {
+    run_service(async move {
+        if let Some(instance) =
+            handle_error_option(&updater, "Can't connect to YouTube Music", connect().await)
+        {
+            updater
+                .send(ManagerMessage::Connected(Arc::new(instance)))
+                .unwrap();
+        }
+    });
+}
+
+async fn app_start_main(
+    updater_r: Receiver<ManagerMessage>,
+    updater_s: Sender<ManagerMessage>,
+    daemon: bool,
+) {
     STARTUP_TIME.log("Init");
 
     std::fs::create_dir_all(CACHE_DIR.join("downloads")).unwrap();
@@ -220,11 +295,78 @@ async fn app_start_main(updater_r: Receiver<ManagerMessage>, updater_s: Sender<M
         }
     }
 
+    let (requests_s, requests_r) = flume::unbounded();
+    // Checked before the player starts, a second daemon would play over the first one
+    #[cfg(unix)]
+    let _socket = match systems::control::spawn_control_system(requests_s.clone()).await {
+        Ok(socket) => Some(socket),
+        Err(e) if daemon => {
+            println!("Can't listen on `{}`: {e}", get_socket_path().display());
+            error!("Can't listen on `{}`: {e}", get_socket_path().display());
+            return;
+        }
+        // The interface works without the socket, only `ytermusic-ctl` can't reach it
+        Err(e) => {
+            log::warn!("Can't listen on `{}`: {e}", get_socket_path().display());
+            None
+        }
+    };
+    #[cfg(not(unix))]
+    let _ = daemon;
+
+    let mpd = &get_config().mpd;
+    if mpd.enabled
+        && let Err(e) = systems::mpd::spawn_mpd_system(mpd.address, requests_s).await
+    {
+        let message = format!("Can't start the MPD server on {}: {e}", mpd.address);
+        report_error(&updater_s, ErrorCategory::Other, message);
+    }
+
+    updater_s.send(ManagerMessage::Connect).unwrap();
+
+    let player = spawn_player_system(updater_s.clone());
+    player
+        .send(PlayerAction::SetCrossfade(get_config().player.crossfade))
+        .unwrap();
+    player
+        .send(PlayerAction::SetNormalization(
+            get_config().player.normalization,
+        ))
+        .unwrap();
+    if let Some(session) = Session::load() {
+        player
+            .send(PlayerAction::Restore(Box::new(session)))
+            .unwrap();
+    }
+
+    if let Some(videos) = DATABASE.read() {
+        DATABASE.clone_from(&videos);
+    }
+    DATABASE.read_loudness();
+
     STARTUP_TIME.log("Startup");
     tasks::clean::spawn_clean_task();
+    tasks::loudness::spawn_loudness_task();
+
+    #[cfg(unix)]
+    if daemon {
+        println!("Listening on `{}`", get_socket_path().display());
+        daemon::Daemon::new(player, updater_s)
+            .run(updater_r, requests_r)
+            .await;
+        shutdown();
+        return;
+    }
+
+    let mut manager = Manager::new(player, updater_s, requests_r);
+    // The terminal is driven from this thread while the runtime runs the other tasks
+    if let Err(e) = tokio::task::block_in_place(|| manager.run(&updater_r)) {
+        error!("Terminal error: {e}");
+    }
+    shutdown();
 }
 
-fn app_start() {
+fn app_start(daemon: bool) {
     let (updater_s, updater_r) = flume::unbounded::<ManagerMessage>();
     let updater_s_c = updater_s.clone();
     ctrlc::set_handler(move || {
@@ -240,7 +382,7 @@ fn app_start() {
             .expect("Failed to build runtime")
             .block_on(async move {
                 select! {
-                    _ = app_start_main(updater_r, updater_s) => {},
+                    _ = app_start_main(updater_r, updater_s, daemon) => {},
                     _ = ShutdownSignal => {},
                 };
             });
@@ -0,0 +1,269 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::utils::get_project_dirs;
+
+/// A command sent to the daemon, one JSON object per line such as `{"command": "next"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Resumes the playback, or plays a video right away
+    Play {
+        #[serde(default)]
+        video_id: Option<String>,
+    },
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    /// Moves to a position in seconds, or by an offset when `relative`
+    Seek {
+        seconds: f64,
+        #[serde(default)]
+        relative: bool,
+    },
+    /// Sets the volume in percent, or changes it by an offset when `relative`
+    Volume {
+        percent: i32,
+        #[serde(default)]
+        relative: bool,
+    },
+    /// Adds videos at the end of the queue, the ones neither local nor returned by a search
+    /// are fetched from YouTube Music
+    Enqueue {
+        video_ids: Vec<String>,
+    },
+    /// Searches the local musics, then YouTube Music when connected
+    Search {
+        query: String,
+    },
+    Status,
+    Queue,
+    /// Plays a video of the queue by its index
+    Select {
+        index: usize,
+    },
+    /// Empties the queue and stops the playback
+    Clear,
+}
+
+/// The answer to a command, on its own line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "kebab-case")]
+pub enum Response {
+    Done,
+    Status(Status),
+    Videos {
+        videos: Vec<YoutubeMusicVideoRef>,
+    },
+    /// The videos of the queue, with the index of the current one
+    Queue {
+        videos: Vec<YoutubeMusicVideoRef>,
+        index: Option<usize>,
+        /// Id of each video, it doesn't change when the queue is edited
+        #[serde(default)]
+        ids: Vec<u32>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub video: Option<YoutubeMusicVideoRef>,
+    pub paused: bool,
+    /// The audio of the video is not downloaded yet
+    pub loading: bool,
+    /// In seconds
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub volume: u8,
+    /// `off`, `one` or `all`
+    pub repeat: String,
+    pub shuffle: bool,
+    /// Index of the video in the queue
+    pub index: Option<usize>,
+    pub queue_length: usize,
+}
+
+/// The socket is in the runtime folder, or in the cache folder on the platforms without one
+pub fn get_socket_path() -> PathBuf {
+    get_project_dirs()
+        .map(|x| x.runtime_dir().unwrap_or(x.cache_dir()).to_owned())
+        .unwrap_or_default()
+        .join("ytermusic.sock")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn video(id: &str) -> YoutubeMusicVideoRef {
+        YoutubeMusicVideoRef {
+            title: format!("Title {id}"),
+            author: "Author".to_string(),
+            album: "Album".to_string(),
+            video_id: id.to_string(),
+            duration: "3:00".to_string(),
+        }
+    }
+
+    /// Checks the json of `value` and reads it back
+    fn round_trip<T>(value: T, expected: Value)
+    where
+        T: Serialize + for<'a> Deserialize<'a> + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(serde_json::to_value(&value).unwrap(), expected);
+        assert_eq!(serde_json::from_value::<T>(expected).unwrap(), value);
+    }
+
+    #[test]
+    fn commands() {
+        let video_id = Some("a".to_string());
+        round_trip(
+            Command::Play { video_id },
+            json!({"command": "play", "video_id": "a"}),
+        );
+        round_trip(
+            Command::Play { video_id: None },
+            json!({"command": "play", "video_id": null}),
+        );
+        round_trip(Command::Pause, json!({"command": "pause"}));
+        round_trip(Command::Toggle, json!({"command": "toggle"}));
+        round_trip(Command::Next, json!({"command": "next"}));
+        round_trip(Command::Previous, json!({"command": "previous"}));
+        round_trip(
+            Command::Seek {
+                seconds: -5.5,
+                relative: true,
+            },
+            json!({"command": "seek", "seconds": -5.5, "relative": true}),
+        );
+        round_trip(
+            Command::Volume {
+                percent: 40,
+                relative: false,
+            },
+            json!({"command": "volume", "percent": 40, "relative": false}),
+        );
+        round_trip(
+            Command::Enqueue {
+                video_ids: vec!["a".to_string(), "b".to_string()],
+            },
+            json!({"command": "enqueue", "video_ids": ["a", "b"]}),
+        );
+        round_trip(
+            Command::Search {
+                query: "song".to_string(),
+            },
+            json!({"command": "search", "query": "song"}),
+        );
+        round_trip(Command::Status, json!({"command": "status"}));
+        round_trip(Command::Queue, json!({"command": "queue"}));
+        round_trip(
+            Command::Select { index: 2 },
+            json!({"command": "select", "index": 2}),
+        );
+        round_trip(Command::Clear, json!({"command": "clear"}));
+    }
+
+    #[test]
+    fn commands_with_omitted_fields() {
+        let read = |x| serde_json::from_value::<Command>(x).unwrap();
+        assert_eq!(
+            read(json!({"command": "play"})),
+            Command::Play { video_id: None }
+        );
+        assert_eq!(
+            read(json!({"command": "seek", "seconds": 10.0})),
+            Command::Seek {
+                seconds: 10.0,
+                relative: false
+            }
+        );
+        assert_eq!(
+            read(json!({"command": "volume", "percent": -5})),
+            Command::Volume {
+                percent: -5,
+                relative: false
+            }
+        );
+        assert!(serde_json::from_value::<Command>(json!({"command": "stop"})).is_err());
+    }
+
+    #[test]
+    fn responses() {
+        round_trip(Response::Done, json!({"result": "done"}));
+        let status = Status {
+            video: Some(video("a")),
+            paused: false,
+            loading: true,
+            position: 1.5,
+            duration: None,
+            volume: 80,
+            repeat: "one".to_string(),
+            shuffle: true,
+            index: Some(0),
+            queue_length: 1,
+        };
+        round_trip(
+            Response::Status(status),
+            json!({
+                "result": "status",
+                "video": video("a"),
+                "paused": false,
+                "loading": true,
+                "position": 1.5,
+                "duration": null,
+                "volume": 80,
+                "repeat": "one",
+                "shuffle": true,
+                "index": 0,
+                "queue_length": 1,
+            }),
+        );
+        round_trip(
+            Response::Videos {
+                videos: vec![video("a")],
+            },
+            json!({"result": "videos", "videos": [video("a")]}),
+        );
+        round_trip(
+            Response::Queue {
+                videos: vec![video("a"), video("b")],
+                index: Some(1),
+                ids: vec![3, 7],
+            },
+            json!({
+                "result": "queue",
+                "videos": [video("a"), video("b")],
+                "index": 1,
+                "ids": [3, 7],
+            }),
+        );
+        round_trip(
+            Response::Error {
+                message: "No video".to_string(),
+            },
+            json!({"result": "error", "message": "No video"}),
+        );
+    }
+
+    #[test]
+    fn queue_without_ids() {
+        let response = json!({"result": "queue", "videos": [video("a")], "index": null});
+        assert_eq!(
+            serde_json::from_value::<Response>(response).unwrap(),
+            Response::Queue {
+                videos: vec![video("a")],
+                index: None,
+                ids: Vec::new(),
+            }
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use std::{
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
     task::Poll,
+    time::{Duration, Instant},
 };
 
 use log::info;
 
 static SHUTDOWN_SENT: AtomicBool = AtomicBool::new(false);
+/// Number of systems still saving their state, the process waits for them before exiting
+static PENDING_GUARDS: AtomicUsize = AtomicUsize::new(0);
 
 pub fn is_shutdown_sent() -> bool {
     SHUTDOWN_SENT.load(Ordering::Relaxed)
@@ -33,3 +36,25 @@ pub fn shutdown() {
     SHUTDOWN_SENT.store(true, Ordering::Relaxed);
     info!("Shutdown signal sent, waiting for shutdown");
 }
+
+/// Delays the exit of the process until it is dropped
+pub struct ShutdownGuard;
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        PENDING_GUARDS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn guard_shutdown() -> ShutdownGuard {
+    PENDING_GUARDS.fetch_add(1, Ordering::SeqCst);
+    ShutdownGuard
+}
+
+/// Waits until every guard is dropped, or until the timeout expires
+pub fn wait_for_guards(timeout: Duration) {
+    let start = Instant::now();
+    while PENDING_GUARDS.load(Ordering::SeqCst) > 0 && start.elapsed() < timeout {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -23,14 +23,16 @@ fn get_handle(updater: &Sender<ManagerMessage>) -> Option<MediaControls> {
 }
 #[cfg(not(target_os = "macos"))]
 pub fn run_window_handler(_updater: &Sender<ManagerMessage>) -> Option<()> {
-    use crate::shutdown::is_shutdown_sent;
+    use crate::shutdown::{is_shutdown_sent, wait_for_guards};
 
     loop {
         if is_shutdown_sent() {
-            use std::process::exit;
+            use std::{process::exit, time::Duration};
 
             use log::info;
 
+            // Lets the player save the session
+            wait_for_guards(Duration::from_secs(2));
             info!("event loop closed");
             exit(0);
         }
@@ -1,2 +1,4 @@
 pub mod media;
 pub mod perfomance;
+pub mod queue;
+pub mod session;
@@ -0,0 +1,448 @@
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+/// Maximum number of videos kept in the history
+const HISTORY_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    One,
+    All,
+}
+
+/// A change of the queue, the player applies it then sends it to the manager
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueAction {
+    /// Adds videos after the current one
+    InsertNext(Vec<YoutubeMusicVideoRef>),
+    Append(Vec<YoutubeMusicVideoRef>),
+    /// Moves the video at the first index to the second one
+    Move(usize, usize),
+    Remove(usize),
+    /// Makes the video at this index the current one
+    Select(usize),
+    Shuffle,
+    /// Restores the order the queue had before the shuffle
+    Unshuffle,
+    SetRepeat(RepeatMode),
+    /// Ends the playback, no video is current anymore
+    Stop,
+    Clear,
+    /// Replaces the whole queue, when the session of the last run is restored
+    Restore(Box<Queue>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "SavedQueue")]
+pub struct Queue {
+    videos: Vec<YoutubeMusicVideoRef>,
+    current: Option<usize>,
+    /// Order of the videos before the shuffle, it follows the insertions and removals
+    original: Option<Vec<YoutubeMusicVideoRef>>,
+    /// Videos played before the current one, the most recent last
+    history: Vec<YoutubeMusicVideoRef>,
+    repeat: RepeatMode,
+    /// Id of each video of the queue, it follows the video when the queue changes
+    #[serde(skip)]
+    ids: Vec<u32>,
+    #[serde(skip)]
+    next_id: u32,
+}
+
+/// A queue as saved in the session, the ids are given again when it is read
+#[derive(Deserialize)]
+struct SavedQueue {
+    videos: Vec<YoutubeMusicVideoRef>,
+    current: Option<usize>,
+    original: Option<Vec<YoutubeMusicVideoRef>>,
+    history: Vec<YoutubeMusicVideoRef>,
+    repeat: RepeatMode,
+}
+
+impl From<SavedQueue> for Queue {
+    fn from(saved: SavedQueue) -> Self {
+        let mut queue = Self {
+            videos: saved.videos,
+            current: saved.current,
+            original: saved.original,
+            history: saved.history,
+            repeat: saved.repeat,
+            ids: Vec::new(),
+            next_id: 0,
+        };
+        queue.ids = queue.new_ids(queue.videos.len());
+        queue
+    }
+}
+
+impl Queue {
+    pub fn videos(&self) -> &[YoutubeMusicVideoRef] {
+        &self.videos
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Ids of the videos, in the order of the queue.
+    /// Unlike the indexes they don't change when other videos are added, moved or removed.
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    pub fn current(&self) -> Option<&YoutubeMusicVideoRef> {
+        self.videos.get(self.current?)
+    }
+
+    /// Videos played after the current one, the whole queue when nothing is playing
+    pub fn upcoming(&self) -> &[YoutubeMusicVideoRef] {
+        &self.videos[self.current.map_or(0, |x| x + 1)..]
+    }
+
+    pub fn history(&self) -> &[YoutubeMusicVideoRef] {
+        &self.history
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.original.is_some()
+    }
+
+    /// Index of the video played when the current one ends
+    pub fn next_index(&self) -> Option<usize> {
+        match self.repeat {
+            RepeatMode::One => self.current,
+            _ => self.skip_index(),
+        }
+    }
+
+    /// Index of the video after the current one, repeat-one doesn't hold the next button
+    pub fn skip_index(&self) -> Option<usize> {
+        let current = self.current?;
+        if current + 1 < self.videos.len() {
+            Some(current + 1)
+        } else if self.repeat != RepeatMode::Off {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    pub fn previous_index(&self) -> Option<usize> {
+        let current = self.current?;
+        if current > 0 {
+            Some(current - 1)
+        } else if self.repeat != RepeatMode::Off {
+            Some(self.videos.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    fn new_ids(&mut self, count: usize) -> Vec<u32> {
+        let start = self.next_id;
+        self.next_id += count as u32;
+        (start..self.next_id).collect()
+    }
+
+    fn leave_current(&mut self) {
+        if let Some(video) = self.current() {
+            self.history.push(video.clone());
+            if self.history.len() > HISTORY_LENGTH {
+                self.history.remove(0);
+            }
+        }
+    }
+
+    /// Applies a change, returns `true` when the current video changed
+    pub fn apply(&mut self, action: QueueAction) -> bool {
+        let previous = self.current().cloned();
+        match action {
+            QueueAction::InsertNext(videos) => {
+                let index = self.current.map_or(0, |x| x + 1);
+                if let Some(original) = &mut self.original {
+                    let position = self
+                        .current
+                        .and_then(|x| original.iter().position(|y| *y == self.videos[x]))
+                        .map_or(0, |x| x + 1);
+                    original.splice(position..position, videos.iter().cloned());
+                }
+                let ids = self.new_ids(videos.len());
+                self.ids.splice(index..index, ids);
+                self.videos.splice(index..index, videos);
+            }
+            QueueAction::Append(videos) => {
+                if let Some(original) = &mut self.original {
+                    original.extend(videos.iter().cloned());
+                }
+                let ids = self.new_ids(videos.len());
+                self.ids.extend(ids);
+                self.videos.extend(videos);
+            }
+            QueueAction::Move(from, to) => {
+                if from >= self.videos.len() || to >= self.videos.len() {
+                    return false;
+                }
+                let video = self.videos.remove(from);
+                self.videos.insert(to, video);
+                let id = self.ids.remove(from);
+                self.ids.insert(to, id);
+                self.current = self.current.map(|x| {
+                    if x == from {
+                        to
+                    } else if from < x && x <= to {
+                        x - 1
+                    } else if to <= x && x < from {
+                        x + 1
+                    } else {
+                        x
+                    }
+                });
+            }
+            QueueAction::Remove(index) => {
+                if index >= self.videos.len() {
+                    return false;
+                }
+                let video = self.videos.remove(index);
+                self.ids.remove(index);
+                if let Some(original) = &mut self.original
+                    && let Some(position) = original.iter().position(|x| *x == video)
+                {
+                    original.remove(position);
+                }
+                self.current = match self.current {
+                    // The following video takes the place of the removed one
+                    Some(x) if x == index && index < self.videos.len() => Some(index),
+                    Some(x) if x == index => None,
+                    Some(x) if x > index => Some(x - 1),
+                    current => current,
+                };
+            }
+            QueueAction::Select(index) => {
+                if index >= self.videos.len() {
+                    return false;
+                }
+                self.leave_current();
+                self.current = Some(index);
+                return true;
+            }
+            QueueAction::Shuffle => {
+                if self.original.is_none() {
+                    self.original = Some(self.videos.clone());
+                }
+                // The ids are shuffled with their videos
+                let mut entries = self
+                    .videos
+                    .drain(..)
+                    .zip(self.ids.drain(..))
+                    .collect::<Vec<_>>();
+                // The current video stays first so the playback continues
+                if let Some(current) = self.current {
+                    let entry = entries.remove(current);
+                    entries.shuffle(&mut rand::rng());
+                    entries.insert(0, entry);
+                    self.current = Some(0);
+                } else {
+                    entries.shuffle(&mut rand::rng());
+                }
+                (self.videos, self.ids) = entries.into_iter().unzip();
+            }
+            QueueAction::Unshuffle => {
+                let Some(original) = self.original.take() else {
+                    return false;
+                };
+                self.current = self
+                    .current()
+                    .and_then(|x| original.iter().position(|y| y == x));
+                // Each video takes back its id, the copies of a video are matched in order
+                let mut entries = self
+                    .videos
+                    .drain(..)
+                    .zip(self.ids.drain(..))
+                    .map(Some)
+                    .collect::<Vec<_>>();
+                for video in &original {
+                    let id = entries
+                        .iter_mut()
+                        .find(|x| x.as_ref().is_some_and(|(y, _)| y == video))
+                        .and_then(Option::take)
+                        .map(|(_, id)| id);
+                    let id = id.unwrap_or_else(|| self.new_ids(1)[0]);
+                    self.ids.push(id);
+                }
+                self.videos = original;
+            }
+            QueueAction::SetRepeat(repeat) => self.repeat = repeat,
+            QueueAction::Stop => {
+                self.leave_current();
+                self.current = None;
+            }
+            QueueAction::Clear => {
+                self.leave_current();
+                self.videos.clear();
+                self.ids.clear();
+                self.original = None;
+                self.current = None;
+            }
+            QueueAction::Restore(queue) => *self = *queue,
+        }
+        self.current() != previous.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str) -> YoutubeMusicVideoRef {
+        YoutubeMusicVideoRef {
+            title: format!("Title {id}"),
+            author: "Author".to_string(),
+            album: "Album".to_string(),
+            video_id: id.to_string(),
+            duration: "3:00".to_string(),
+        }
+    }
+
+    /// A queue of `a` to `e` playing `c`
+    fn queue() -> Queue {
+        let mut queue = Queue::default();
+        queue.apply(QueueAction::Append(
+            ["a", "b", "c", "d", "e"].into_iter().map(video).collect(),
+        ));
+        queue.apply(QueueAction::Select(2));
+        queue
+    }
+
+    fn ids(queue: &Queue) -> Vec<&str> {
+        queue.videos().iter().map(|x| x.video_id.as_str()).collect()
+    }
+
+    #[test]
+    fn move_before_current() {
+        let mut queue = queue();
+        assert!(!queue.apply(QueueAction::Move(4, 0)));
+        assert_eq!(ids(&queue), ["e", "a", "b", "c", "d"]);
+        assert_eq!(queue.current_index(), Some(3));
+        assert!(!queue.apply(QueueAction::Move(1, 4)));
+        assert_eq!(ids(&queue), ["e", "b", "c", "d", "a"]);
+        assert_eq!(queue.current_index(), Some(2));
+    }
+
+    #[test]
+    fn move_after_current() {
+        let mut queue = queue();
+        assert!(!queue.apply(QueueAction::Move(3, 4)));
+        assert_eq!(ids(&queue), ["a", "b", "c", "e", "d"]);
+        assert_eq!(queue.current_index(), Some(2));
+    }
+
+    #[test]
+    fn move_current() {
+        let mut queue = queue();
+        assert!(!queue.apply(QueueAction::Move(2, 0)));
+        assert_eq!(ids(&queue), ["c", "a", "b", "d", "e"]);
+        assert_eq!(queue.current_index(), Some(0));
+        assert!(!queue.apply(QueueAction::Move(0, 5)));
+    }
+
+    #[test]
+    fn remove_earlier() {
+        let mut queue = queue();
+        assert!(!queue.apply(QueueAction::Remove(0)));
+        assert_eq!(ids(&queue), ["b", "c", "d", "e"]);
+        assert_eq!(queue.current().unwrap().video_id, "c");
+        assert!(!queue.apply(QueueAction::Remove(3)));
+        assert_eq!(queue.current_index(), Some(1));
+    }
+
+    #[test]
+    fn remove_current() {
+        let mut queue = queue();
+        assert!(queue.apply(QueueAction::Remove(2)));
+        assert_eq!(ids(&queue), ["a", "b", "d", "e"]);
+        assert_eq!(queue.current().unwrap().video_id, "d");
+
+        // Nothing follows the last video
+        queue.apply(QueueAction::Select(3));
+        assert!(queue.apply(QueueAction::Remove(3)));
+        assert_eq!(queue.current_index(), None);
+    }
+
+    #[test]
+    fn unshuffle_restores_position() {
+        let mut queue = queue();
+        assert!(!queue.apply(QueueAction::Shuffle));
+        assert!(queue.is_shuffled());
+        assert_eq!(queue.current_index(), Some(0));
+        assert_eq!(queue.current().unwrap().video_id, "c");
+
+        assert!(!queue.apply(QueueAction::Unshuffle));
+        assert!(!queue.is_shuffled());
+        assert_eq!(ids(&queue), ["a", "b", "c", "d", "e"]);
+        assert_eq!(queue.current_index(), Some(2));
+    }
+
+    #[test]
+    fn unshuffle_follows_changes() {
+        let mut queue = queue();
+        queue.apply(QueueAction::Shuffle);
+        queue.apply(QueueAction::InsertNext(vec![video("f")]));
+        let index = queue
+            .videos()
+            .iter()
+            .position(|x| x.video_id == "a")
+            .unwrap();
+        queue.apply(QueueAction::Remove(index));
+
+        queue.apply(QueueAction::Unshuffle);
+        assert_eq!(ids(&queue), ["b", "c", "f", "d", "e"]);
+        assert_eq!(queue.current().unwrap().video_id, "c");
+    }
+
+    #[test]
+    fn entry_ids_follow_videos() {
+        let mut queue = queue();
+        let id_of = |queue: &Queue, video_id: &str| {
+            let index = queue.videos().iter().position(|x| x.video_id == video_id);
+            queue.ids()[index.unwrap()]
+        };
+        let before = ["a", "b", "c", "d", "e"].map(|x| id_of(&queue, x));
+
+        queue.apply(QueueAction::Move(4, 0));
+        queue.apply(QueueAction::Remove(1));
+        queue.apply(QueueAction::Shuffle);
+        queue.apply(QueueAction::Unshuffle);
+        assert_eq!(ids(&queue), ["e", "b", "c", "d"]);
+        for (video_id, id) in ["b", "c", "d", "e"].into_iter().zip(&before[1..]) {
+            assert_eq!(id_of(&queue, video_id), *id);
+        }
+
+        queue.apply(QueueAction::Append(vec![video("a")]));
+        assert!(!before.contains(&id_of(&queue, "a")));
+    }
+
+    #[test]
+    fn deserialized_queue_has_ids() {
+        let json = serde_json::to_string(&queue()).unwrap();
+        let mut queue = serde_json::from_str::<Queue>(&json).unwrap();
+        assert_eq!(queue.videos().len(), queue.ids().len());
+
+        queue.apply(QueueAction::Remove(0));
+        assert_eq!(queue.videos().len(), queue.ids().len());
+        queue.apply(QueueAction::Move(3, 0));
+        assert_eq!(queue.videos().len(), queue.ids().len());
+        queue.apply(QueueAction::Shuffle);
+        assert_eq!(queue.videos().len(), 4);
+        assert_eq!(queue.videos().len(), queue.ids().len());
+        queue.apply(QueueAction::Unshuffle);
+        assert_eq!(ids(&queue), ["e", "b", "c", "d"]);
+        assert_eq!(queue.videos().len(), queue.ids().len());
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::{
+    consts::CACHE_DIR,
+    structures::queue::{Queue, QueueAction},
+    systems::player::get_audio_file,
+};
+
+/// Bumped when the format changes, older sessions are then ignored
+const SESSION_VERSION: u32 = 1;
+
+/// State of the player saved at shutdown and restored at startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub queue: Queue,
+    /// Position in the current video
+    pub position: Duration,
+    pub volume: u8,
+}
+
+impl Session {
+    pub fn new(queue: Queue, position: Duration, volume: u8) -> Self {
+        Self {
+            version: SESSION_VERSION,
+            queue,
+            position,
+            volume,
+        }
+    }
+
+    /// Reads the session of the last run, the videos whose audio isn't cached anymore are removed
+    pub fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(CACHE_DIR.join("session.json")).ok()?;
+        let value = serde_json::from_str::<serde_json::Value>(&content).ok()?;
+        let version = value.get("version").and_then(|x| x.as_u64());
+        if version != Some(SESSION_VERSION as u64) {
+            warn!("Ignoring the session saved with version {version:?}");
+            return None;
+        }
+        let mut session = match serde_json::from_value::<Session>(value) {
+            Ok(session) => session,
+            Err(e) => {
+                error!("Can't read the session: {e}");
+                return None;
+            }
+        };
+        session.retain_cached(|x| get_audio_file(x).is_some());
+        info!("Restoring {} videos", session.queue.videos().len());
+        Some(session)
+    }
+
+    /// Removes the videos whose audio isn't cached,
+    /// the position is reset when the current video is one of them
+    fn retain_cached(&mut self, is_cached: impl Fn(&YoutubeMusicVideoRef) -> bool) {
+        let current = self.queue.current().cloned();
+        for index in (0..self.queue.videos().len()).rev() {
+            let video = &self.queue.videos()[index];
+            if !is_cached(video) {
+                warn!(
+                    "Removing {} from the session, its audio isn't cached",
+                    video.video_id
+                );
+                self.queue.apply(QueueAction::Remove(index));
+            }
+        }
+        if self.queue.current() != current.as_ref() {
+            self.position = Duration::ZERO;
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|x| {
+                std::fs::write(CACHE_DIR.join("session.json"), x).map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => info!("Session saved"),
+            Err(e) => error!("Can't save the session: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str) -> YoutubeMusicVideoRef {
+        YoutubeMusicVideoRef {
+            title: format!("Title {id}"),
+            author: "Author".to_string(),
+            album: "Album".to_string(),
+            video_id: id.to_string(),
+            duration: "3:00".to_string(),
+        }
+    }
+
+    /// A session of `a`, `b` and `c` playing `b` at 42s, read back from its json
+    fn saved_session() -> Session {
+        let mut queue = Queue::default();
+        queue.apply(QueueAction::Append(["a", "b", "c"].map(video).to_vec()));
+        queue.apply(QueueAction::Select(1));
+        let session = Session::new(queue, Duration::from_secs(42), 80);
+        serde_json::from_str(&serde_json::to_string(&session).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn removes_earlier_video() {
+        let mut session = saved_session();
+        session.retain_cached(|x| x.video_id != "a");
+        assert_eq!(session.queue.videos(), ["b", "c"].map(video));
+        assert_eq!(session.queue.current_index(), Some(0));
+        assert_eq!(session.queue.ids().len(), 2);
+        assert_eq!(session.position, Duration::from_secs(42));
+        assert_eq!(session.volume, 80);
+    }
+
+    #[test]
+    fn removes_current_video() {
+        let mut session = saved_session();
+        session.retain_cached(|x| x.video_id != "b");
+        assert_eq!(session.queue.videos(), ["a", "c"].map(video));
+        assert_eq!(session.queue.current().unwrap().video_id, "c");
+        assert_eq!(session.position, Duration::ZERO);
+    }
+
+    #[test]
+    fn removes_every_video() {
+        let mut session = saved_session();
+        session.retain_cached(|_| false);
+        assert!(session.queue.videos().is_empty());
+        assert_eq!(session.queue.current_index(), None);
+        assert_eq!(session.position, Duration::ZERO);
+    }
+}
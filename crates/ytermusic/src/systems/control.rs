@@ -0,0 +1,363 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use flume::Sender;
+use log::warn;
+use ytapi2::{instance::YoutubeMusicInstance, types::YoutubeMusicVideoRef};
+
+#[cfg(unix)]
+use {
+    log::info,
+    std::{fs, io, os::unix::fs::PermissionsExt},
+    tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{UnixListener, UnixStream},
+    },
+};
+
+use crate::{
+    database::{DATABASE, search_local},
+    protocol::{Command, Response, Status},
+    run_service,
+    shutdown::ShutdownGuard,
+    structures::queue::{Queue, QueueAction, RepeatMode},
+    systems::player::{PlayerAction, PlayerState},
+    term::ManagerMessage,
+};
+#[cfg(unix)]
+use crate::{protocol::get_socket_path, shutdown::guard_shutdown};
+
+/// A command with the channel its response is sent to
+pub type Request = (Command, Sender<Response>);
+
+/// Answers the commands of the control socket from the state published by the player
+pub struct Controller {
+    player: Sender<PlayerAction>,
+    instance: Option<Arc<YoutubeMusicInstance>>,
+    state: Option<PlayerState>,
+    queue: Queue,
+    /// Videos returned by the searches, by id, so they can be enqueued
+    found: Arc<RwLock<HashMap<String, YoutubeMusicVideoRef>>>,
+}
+
+impl Controller {
+    pub fn new(player: Sender<PlayerAction>) -> Self {
+        Self {
+            player,
+            instance: None,
+            state: None,
+            queue: Queue::default(),
+            found: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Keeps the state of the player and the connection up to date
+    pub fn observe(&mut self, message: &ManagerMessage) {
+        match message {
+            ManagerMessage::Connected(instance) => self.instance = Some(instance.clone()),
+            ManagerMessage::PlayerState(state) => self.state = Some(state.clone()),
+            ManagerMessage::Queue(_, queue) => self.queue = (**queue).clone(),
+            _ => (),
+        }
+    }
+
+    /// Answers right away, except the searches which answer once YouTube Music replied
+    pub fn handle_command(&mut self, command: Command, response: Sender<Response>) {
+        let state = self.state.as_ref();
+        let paused = state.is_none_or(|x| x.paused);
+        let answer = match command {
+            Command::Play { video_id: None } => {
+                if self.queue.current().is_none() && !self.queue.videos().is_empty() {
+                    self.send(PlayerAction::Edit(QueueAction::Select(0)));
+                } else if paused {
+                    self.send(PlayerAction::PlayPause);
+                }
+                Response::Done
+            }
+            Command::Play {
+                video_id: Some(video_id),
+            } => {
+                return self.with_videos(vec![video_id], response, |mut videos| {
+                    PlayerAction::Play(videos.remove(0))
+                });
+            }
+            Command::Pause => {
+                if !paused {
+                    self.send(PlayerAction::PlayPause);
+                }
+                Response::Done
+            }
+            Command::Toggle => {
+                self.send(PlayerAction::PlayPause);
+                Response::Done
+            }
+            Command::Next => {
+                self.send(PlayerAction::Next);
+                Response::Done
+            }
+            Command::Previous => {
+                self.send(PlayerAction::Previous);
+                Response::Done
+            }
+            Command::Seek { seconds, relative } => {
+                let current = state.map_or(0., |x| x.position.as_secs_f64());
+                let position = if relative { current + seconds } else { seconds };
+                match Duration::try_from_secs_f64(position.max(0.)) {
+                    Ok(position) => {
+                        self.send(PlayerAction::Seek(position));
+                        Response::Done
+                    }
+                    Err(_) => Response::Error {
+                        message: format!("Can't seek to {seconds} seconds"),
+                    },
+                }
+            }
+            Command::Volume { percent, relative } => {
+                let current = state.map_or(0, |x| x.volume as i32);
+                let volume = if relative { current + percent } else { percent };
+                self.send(PlayerAction::SetVolume(volume.clamp(0, 100) as u8));
+                Response::Done
+            }
+            Command::Enqueue { video_ids } => {
+                return self.with_videos(video_ids, response, PlayerAction::Queue);
+            }
+            Command::Search { query } => return self.search(query, response),
+            Command::Status => Response::Status(self.status()),
+            Command::Queue => Response::Queue {
+                videos: self.queue.videos().to_vec(),
+                index: self.queue.current_index(),
+                ids: self.queue.ids().to_vec(),
+            },
+            Command::Select { index } if index < self.queue.videos().len() => {
+                self.send(PlayerAction::Edit(QueueAction::Select(index)));
+                Response::Done
+            }
+            Command::Select { index } => Response::Error {
+                message: format!("No video at index {index} of the queue"),
+            },
+            Command::Clear => {
+                self.send(PlayerAction::Edit(QueueAction::Clear));
+                Response::Done
+            }
+        };
+        let _ = response.send(answer);
+    }
+
+    fn send(&self, action: PlayerAction) {
+        self.player.send(action).unwrap();
+    }
+
+    /// Looks for the videos in the local musics, the queue and the search results
+    fn find_videos(&self, video_ids: &[String]) -> Vec<Option<YoutubeMusicVideoRef>> {
+        let local = DATABASE.videos();
+        let found = self.found.read().unwrap();
+        video_ids
+            .iter()
+            .map(|id| {
+                local
+                    .iter()
+                    .chain(self.queue.videos())
+                    .find(|x| x.video_id == *id)
+                    .or_else(|| found.get(id))
+                    .cloned()
+            })
+            .collect()
+    }
+
+    /// Sends the action made from the videos, in the order of the ids.
+    /// The videos that aren't known yet are fetched from YouTube Music when connected.
+    fn with_videos(
+        &self,
+        video_ids: Vec<String>,
+        response: Sender<Response>,
+        action: fn(Vec<YoutubeMusicVideoRef>) -> PlayerAction,
+    ) {
+        let known = self.find_videos(&video_ids);
+        if known.iter().all(Option::is_some) {
+            self.send(action(known.into_iter().flatten().collect()));
+            let _ = response.send(Response::Done);
+            return;
+        }
+        let Some(instance) = self.instance.clone() else {
+            let id = video_ids.iter().zip(&known).find(|(_, x)| x.is_none());
+            let message = format!("Unknown video `{}`, search it first", id.unwrap().0);
+            let _ = response.send(Response::Error { message });
+            return;
+        };
+        let player = self.player.clone();
+        let found = self.found.clone();
+        run_service(async move {
+            let mut videos = Vec::new();
+            for (id, video) in video_ids.into_iter().zip(known) {
+                let video = match video {
+                    Some(video) => video,
+                    None => match fetch_video(&instance, &id).await {
+                        Some(video) => {
+                            found.write().unwrap().insert(id, video.clone());
+                            video
+                        }
+                        None => {
+                            let message = format!("Can't find the video `{id}`");
+                            let _ = response.send(Response::Error { message });
+                            return;
+                        }
+                    },
+                };
+                videos.push(video);
+            }
+            player.send(action(videos)).unwrap();
+            let _ = response.send(Response::Done);
+        });
+    }
+
+    /// Only the local musics are searched until connected
+    fn search(&self, query: String, response: Sender<Response>) {
+        let local = search_local(&query);
+        let Some(instance) = self.instance.clone() else {
+            let _ = response.send(Response::Videos { videos: local });
+            return;
+        };
+        let found = self.found.clone();
+        run_service(async move {
+            let mut videos = local;
+            match instance.search(&query).await {
+                Ok(results) => {
+                    let online = results.songs.into_iter().chain(results.videos);
+                    let mut found = found.write().unwrap();
+                    for video in online {
+                        if !videos.iter().any(|x| x.video_id == video.video_id) {
+                            found.insert(video.video_id.clone(), video.clone());
+                            videos.push(video);
+                        }
+                    }
+                }
+                Err(e) => warn!("Can't search `{query}`: {e:?}"),
+            }
+            let _ = response.send(Response::Videos { videos });
+        });
+    }
+
+    fn status(&self) -> Status {
+        let state = self.state.as_ref();
+        Status {
+            video: state.and_then(|x| x.video.clone()),
+            paused: state.is_none_or(|x| x.paused),
+            loading: state.is_some_and(|x| x.loading),
+            position: state.map_or(0., |x| x.position.as_secs_f64()),
+            duration: state.and_then(|x| x.duration).map(|x| x.as_secs_f64()),
+            volume: state.map_or(0, |x| x.volume),
+            repeat: match self.queue.repeat() {
+                RepeatMode::Off => "off",
+                RepeatMode::One => "one",
+                RepeatMode::All => "all",
+            }
+            .to_string(),
+            shuffle: self.queue.is_shuffled(),
+            index: self.queue.current_index(),
+            queue_length: self.queue.videos().len(),
+        }
+    }
+}
+
+/// The title and the artist of a video are read from its radio, which starts with it
+async fn fetch_video(
+    instance: &YoutubeMusicInstance,
+    video_id: &str,
+) -> Option<YoutubeMusicVideoRef> {
+    match instance.get_radio(video_id).await {
+        Ok((videos, _)) => videos.into_iter().find(|x| x.video_id == video_id),
+        Err(e) => {
+            warn!("Can't fetch the video `{video_id}`: {e:?}");
+            None
+        }
+    }
+}
+
+/// Removes the socket when dropped, the exit waits for it
+pub struct ControlSocket {
+    _guard: ShutdownGuard,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Err(e) = std::fs::remove_file(get_socket_path()) {
+            warn!("Can't remove the control socket: {e}");
+        }
+    }
+}
+
+/// Listens on the control socket, the commands are answered by the receiver of the requests
+#[cfg(unix)]
+pub async fn spawn_control_system(requests: Sender<Request>) -> io::Result<ControlSocket> {
+    let path = get_socket_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if path.exists() {
+        // The socket left by a crash is replaced, not the one of a running instance
+        if UnixStream::connect(&path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("An instance already listens on `{}`", path.display()),
+            ));
+        }
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    let socket = ControlSocket {
+        _guard: guard_shutdown(),
+    };
+    // Only the user can control the player
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    info!("Listening on `{}`", path.display());
+
+    run_service(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    run_service(handle_connection(stream, requests.clone()));
+                }
+                Err(e) => warn!("Can't accept a control connection: {e}"),
+            }
+        }
+    });
+    Ok(socket)
+}
+
+/// Answers the commands of a client until it disconnects
+#[cfg(unix)]
+async fn handle_connection(stream: UnixStream, requests: Sender<Request>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (response_s, response_r) = flume::bounded(1);
+                if requests.send_async((command, response_s)).await.is_err() {
+                    break;
+                }
+                response_r
+                    .recv_async()
+                    .await
+                    .unwrap_or_else(|_| Response::Error {
+                        message: "The player is stopping".to_string(),
+                    })
+            }
+            Err(e) => Response::Error {
+                message: format!("Invalid command: {e}"),
+            },
+        };
+        let mut json = serde_json::to_string(&response).unwrap();
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
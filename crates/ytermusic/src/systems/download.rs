@@ -0,0 +1,341 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use database::DASH_HEADER;
+use flume::{Receiver, Sender};
+use log::{error, info, trace, warn};
+use once_cell::sync::Lazy;
+use reqwest::{StatusCode, header::RANGE};
+use rodio::{Decoder, Source};
+use tokio::{
+    fs::OpenOptions,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+use ytapi2::{
+    instance::YoutubeMusicInstance,
+    types::{YoutubeMusicAudioFormat, YoutubeMusicVideoRef},
+};
+
+use crate::{
+    config::{AudioQuality, get_config},
+    consts::CACHE_DIR,
+    database::DATABASE,
+    run_service,
+    systems::loudness::{YOUTUBE_REFERENCE_LUFS, measure_file},
+    term::ManagerMessage,
+};
+
+/// Attempts made on a video before it is reported as failed
+const MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled after each failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Gap allowed between the decoded duration and the one announced by the player,
+/// when the size of the stream is unknown
+const DURATION_TOLERANCE: f64 = 2.;
+
+static QUEUE: Lazy<(Sender<YoutubeMusicVideoRef>, Receiver<YoutubeMusicVideoRef>)> =
+    Lazy::new(flume::unbounded);
+
+/// Ids of the videos queued or being downloaded, so a video is never downloaded twice
+static IN_DOWNLOAD: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadStatus {
+    Queued,
+    /// Percentage of the audio file received
+    Downloading(u8),
+    /// Number of the attempt that failed, the download restarts after a delay
+    Retrying(u32),
+    Done,
+    Failed(String),
+}
+
+pub fn get_download_path(video_id: &str) -> PathBuf {
+    CACHE_DIR.join("downloads").join(format!("{video_id}.mp4"))
+}
+
+/// A video is downloaded once its sidecar is written, the audio file is complete at this point
+pub fn is_downloaded(video_id: &str) -> bool {
+    CACHE_DIR
+        .join("downloads")
+        .join(format!("{video_id}.json"))
+        .exists()
+}
+
+/// Adds a video to the download queue, does nothing if it is already downloaded or queued
+pub fn download(video: YoutubeMusicVideoRef, updater: &Sender<ManagerMessage>) {
+    if is_downloaded(&video.video_id) {
+        return;
+    }
+    if !IN_DOWNLOAD.lock().unwrap().insert(video.video_id.clone()) {
+        return;
+    }
+    updater
+        .send(ManagerMessage::Download(
+            video.clone(),
+            DownloadStatus::Queued,
+        ))
+        .unwrap();
+    QUEUE.0.send(video).unwrap();
+}
+
+pub fn spawn_download_system(instance: Arc<YoutubeMusicInstance>, updater: Sender<ManagerMessage>) {
+    resume_partial_downloads(&updater);
+    for _ in 0..get_config().download.concurrency {
+        let instance = instance.clone();
+        let updater = updater.clone();
+        run_service(async move {
+            while let Ok(video) = QUEUE.1.recv_async().await {
+                let status = download_with_retries(&instance, &video, &updater).await;
+                IN_DOWNLOAD.lock().unwrap().remove(&video.video_id);
+                updater
+                    .send(ManagerMessage::Download(video, status))
+                    .unwrap();
+            }
+        });
+    }
+}
+
+/// Queues again the downloads interrupted by a restart, their partial sidecar holds the video
+fn resume_partial_downloads(updater: &Sender<ManagerMessage>) {
+    let Ok(entries) = std::fs::read_dir(CACHE_DIR.join("downloads")) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.to_string_lossy().ends_with(".json.part") {
+            continue;
+        }
+        let video = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|x| serde_json::from_str::<YoutubeMusicVideoRef>(&x).ok());
+        match video {
+            Some(video) => {
+                info!("Resuming the download of {}", video.video_id);
+                download(video, updater);
+            }
+            None => warn!("Invalid partial sidecar {}", path.display()),
+        }
+    }
+}
+
+async fn download_with_retries(
+    instance: &YoutubeMusicInstance,
+    video: &YoutubeMusicVideoRef,
+    updater: &Sender<ManagerMessage>,
+) -> DownloadStatus {
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        match download_video(instance, video, updater).await {
+            Ok(()) => {
+                info!("Downloaded {}", video.video_id);
+                return DownloadStatus::Done;
+            }
+            Err(e) if attempt == MAX_ATTEMPTS => {
+                error!("Can't download {}: {e}", video.video_id);
+                return DownloadStatus::Failed(e);
+            }
+            Err(e) => {
+                warn!(
+                    "Attempt {attempt} to download {} failed: {e}",
+                    video.video_id
+                );
+                updater
+                    .send(ManagerMessage::Download(
+                        video.clone(),
+                        DownloadStatus::Retrying(attempt),
+                    ))
+                    .unwrap();
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Picks the mp4 audio stream of the configured quality,
+/// they start with the header checked by the database
+fn get_best_format(formats: &[YoutubeMusicAudioFormat]) -> Option<&YoutubeMusicAudioFormat> {
+    let formats = formats
+        .iter()
+        .filter(|x| x.mime_type.starts_with("audio/mp4") && x.url.is_some());
+    match get_config().download.quality {
+        AudioQuality::High => formats.max_by_key(|x| x.bitrate),
+        AudioQuality::Low => formats.min_by_key(|x| x.bitrate),
+    }
+}
+
+async fn download_video(
+    instance: &YoutubeMusicInstance,
+    video: &YoutubeMusicVideoRef,
+    updater: &Sender<ManagerMessage>,
+) -> Result<(), String> {
+    let player = instance
+        .get_player_deciphered(&video.video_id, &CACHE_DIR.join("players"))
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+    let format = get_best_format(&player.formats).ok_or("No mp4 audio stream available")?;
+    trace!("Downloading {} with format {}", video.video_id, format.itag);
+
+    let path = get_download_path(&video.video_id);
+    let part_path = path.with_extension("mp4.part");
+    // Written first so the download can be resumed after a restart
+    let sidecar_part_path = path.with_extension("json.part");
+    let sidecar = serde_json::to_string(video).map_err(|e| e.to_string())?;
+    tokio::fs::write(&sidecar_part_path, sidecar)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let total = write_stream(video, format, &part_path, updater).await?;
+    if let Err(e) = check_file(&part_path, total).await {
+        // Resuming a corrupted file would never give a valid one
+        let _ = tokio::fs::remove_file(&part_path).await;
+        return Err(e);
+    }
+    if total.is_none() {
+        let decoded = {
+            let part_path = part_path.clone();
+            tokio::task::spawn_blocking(move || decode_duration(&part_path))
+                .await
+                .ok()
+                .flatten()
+        };
+        let duration = player.duration_seconds;
+        if !matches!((duration, decoded), (Some(duration), Some(decoded))
+            if (duration as f64 - decoded).abs() <= DURATION_TOLERANCE)
+        {
+            // The file may be complete, it stays partial and the next attempt checks it again
+            return Err(format!(
+                "Can't confirm that the audio file is complete, it decodes to {decoded:?}s out of {duration:?}s"
+            ));
+        }
+    }
+    tokio::fs::rename(&part_path, &path)
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::fs::rename(&sidecar_part_path, path.with_extension("json"))
+        .await
+        .map_err(|e| e.to_string())?;
+    DATABASE.append(video.clone());
+
+    match format.loudness_db.or(player.loudness_db) {
+        Some(loudness) => DATABASE.set_loudness(&video.video_id, YOUTUBE_REFERENCE_LUFS + loudness),
+        None => {
+            let measured = tokio::task::spawn_blocking(move || measure_file(&path)).await;
+            if let Ok(Some(lufs)) = measured {
+                DATABASE.set_loudness(&video.video_id, lufs);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Appends the stream to the partial file, starting where a previous attempt stopped.
+/// Returns the size of the complete stream, when the player or the server announced it.
+async fn write_stream(
+    video: &YoutubeMusicVideoRef,
+    format: &YoutubeMusicAudioFormat,
+    path: &Path,
+    updater: &Sender<ManagerMessage>,
+) -> Result<Option<u64>, String> {
+    let mut received = tokio::fs::metadata(path)
+        .await
+        .map(|x| x.len())
+        .unwrap_or(0);
+    if let Some(total) = format.content_length
+        && received >= total
+    {
+        // Either complete or larger than the stream, the size check decides
+        return Ok(Some(total));
+    }
+    if received > 0 {
+        info!("Resuming {} from byte {received}", video.video_id);
+    }
+
+    let response = reqwest::Client::new()
+        .get(format.url.as_deref().unwrap_or_default())
+        .header(RANGE, format!("bytes={received}-"))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && received > 0 {
+        // Nothing past the end of the partial file, the checks decide if it is complete
+        return Ok(format.content_length);
+    }
+    let mut response = response.error_for_status().map_err(|e| e.to_string())?;
+    let mut file = if response.status() == StatusCode::PARTIAL_CONTENT {
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await
+    } else {
+        // The server ignored the range and sends the whole file
+        received = 0;
+        tokio::fs::File::create(path).await
+    }
+    .map_err(|e| e.to_string())?;
+    let total = format
+        .content_length
+        .or_else(|| response.content_length().map(|x| x + received));
+
+    let mut last_progress = 0;
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        received += chunk.len() as u64;
+        if let Some(total) = total {
+            let progress = (received * 100 / total.max(1)).min(100) as u8;
+            if progress != last_progress {
+                last_progress = progress;
+                updater
+                    .send(ManagerMessage::Download(
+                        video.clone(),
+                        DownloadStatus::Downloading(progress),
+                    ))
+                    .unwrap();
+            }
+        }
+    }
+    file.flush().await.map_err(|e| e.to_string())?;
+    Ok(total)
+}
+
+/// Checks that the partial file has the size of the stream, when it is known,
+/// and starts like an audio file before it is renamed
+async fn check_file(path: &Path, total: Option<u64>) -> Result<(), String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let size = file.metadata().await.map_err(|e| e.to_string())?.len();
+    if let Some(total) = total
+        && size != total
+    {
+        return Err(format!("The audio file has {size} bytes out of {total}"));
+    }
+    let mut header = [0; DASH_HEADER.len()];
+    if file.read_exact(&mut header).await.is_err() || header != DASH_HEADER {
+        return Err("The audio file doesn't start with a ftypdash header".to_string());
+    }
+    Ok(())
+}
+
+/// Decodes the whole file and returns its duration in seconds
+fn decode_duration(path: &Path) -> Option<f64> {
+    let file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let decoder = Decoder::builder()
+        .with_data(BufReader::new(file))
+        .with_byte_len(length)
+        .with_hint("mp4")
+        .build()
+        .ok()?;
+    let samples_per_second = decoder.sample_rate() as f64 * decoder.channels() as f64;
+    Some(decoder.count() as f64 / samples_per_second)
+}
@@ -1,14 +1,14 @@
 use flume::Sender;
-use log::{Level, LevelFilter, Metadata, Record, SetLoggerError, info};
+use log::{LevelFilter, Metadata, Record, SetLoggerError, info};
 use once_cell::sync::Lazy;
 
 use std::{io::Write, path::PathBuf};
 
-use crate::utils::get_project_dirs;
+use crate::{config::get_config, consts::CACHE_DIR};
 
 pub fn init() -> Result<(), SetLoggerError> {
-    log::set_logger(&LOGGER).map(|()| log::set_max_level(LEVEL.0))?;
-    info!("Logger mode {}", LEVEL.1);
+    log::set_logger(&LOGGER).map(|()| log::set_max_level(*LEVEL))?;
+    info!("Logger mode {}", *LEVEL);
     Ok(())
 }
 
@@ -40,7 +40,7 @@ static FILTER: &[&str] = &["rustls", "tokio-util", "want-", "mio-"];
 
 impl log::Log for SimpleLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= LEVEL.1
+        metadata.level() <= *LEVEL
     }
 
     fn log(&self, record: &Record) {
@@ -62,26 +62,18 @@ impl log::Log for SimpleLogger {
 }
 
 static LOGGER: SimpleLogger = SimpleLogger;
-static LEVEL: Lazy<(LevelFilter, Level)> = Lazy::new(|| {
-    let logger_env = std::env::var("YTMUSIC_LOG");
-    if let Ok(logger_env) = logger_env {
-        if logger_env == "true" {
-            (LevelFilter::Trace, Level::Trace)
-        } else {
-            (LevelFilter::Info, Level::Info)
-        }
+/// `YTMUSIC_LOG=true` enables the traces whatever the configured level
+static LEVEL: Lazy<LevelFilter> = Lazy::new(|| {
+    if std::env::var("YTMUSIC_LOG").is_ok_and(|x| x == "true") {
+        LevelFilter::Trace
     } else {
-        (LevelFilter::Info, Level::Info)
+        get_config().log_level.unwrap_or(LevelFilter::Info)
     }
 });
 
 pub fn get_log_file_path() -> PathBuf {
-    if let Some(val) = get_project_dirs() {
-        if let Err(e) = std::fs::create_dir_all(val.cache_dir()) {
-            panic!("Failed to create cache dir: {}", e);
-        }
-        val.cache_dir().join("log.txt")
-    } else {
-        PathBuf::from("log.txt")
+    if let Err(e) = std::fs::create_dir_all(&*CACHE_DIR) {
+        panic!("Failed to create cache dir: {}", e);
     }
+    CACHE_DIR.join("log.txt")
 }
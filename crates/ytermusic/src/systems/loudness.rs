@@ -0,0 +1,170 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use rodio::{Decoder, Source};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::database::DATABASE;
+
+/// `loudnessDb` of the player endpoint is relative to this level
+pub const YOUTUBE_REFERENCE_LUFS: f64 = -14.;
+/// Quiet tracks aren't boosted further, to keep the peaks from clipping
+const MAX_GAIN_DB: f64 = 6.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationMode {
+    Off,
+    /// Each track is brought to the target
+    Track,
+    /// The tracks of an album share a gain, so their relative levels are kept
+    Album,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub mode: NormalizationMode,
+    pub target_lufs: f64,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            mode: NormalizationMode::Track,
+            target_lufs: YOUTUBE_REFERENCE_LUFS,
+        }
+    }
+}
+
+impl Normalization {
+    /// Gain to apply to a track, zero when its loudness isn't known yet
+    pub fn get_gain_db(&self, video: &YoutubeMusicVideoRef) -> f64 {
+        let loudness = match self.mode {
+            NormalizationMode::Off => None,
+            NormalizationMode::Track => DATABASE.get_loudness(&video.video_id),
+            NormalizationMode::Album => DATABASE
+                .get_album_loudness(&video.album)
+                .filter(|_| !video.album.is_empty())
+                .or_else(|| DATABASE.get_loudness(&video.video_id)),
+        };
+        loudness.map_or(0., |x| (self.target_lufs - x).min(MAX_GAIN_DB))
+    }
+}
+
+/// Decodes a cached file and measures its integrated loudness in LUFS
+pub fn measure_file(path: &Path) -> Option<f64> {
+    let file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let decoder = Decoder::builder()
+        .with_data(BufReader::new(file))
+        .with_byte_len(length)
+        .with_hint(&path.extension().unwrap_or_default().to_string_lossy())
+        .build()
+        .ok()?;
+    let channels = decoder.channels() as usize;
+    let sample_rate = decoder.sample_rate();
+    measure_loudness(decoder, channels, sample_rate)
+}
+
+/// Integrated loudness of interleaved samples as defined by EBU R128 (ITU-R BS.1770),
+/// `None` for silence
+pub fn measure_loudness(
+    samples: impl Iterator<Item = f32>,
+    channels: usize,
+    sample_rate: u32,
+) -> Option<f64> {
+    // Energy of the 100ms steps, the gating blocks are made of 4 steps
+    let step = sample_rate as usize / 10;
+    let mut filters = vec![KWeighting::new(sample_rate as f64); channels];
+    let mut steps = Vec::new();
+    let (mut sum, mut channel, mut frames) = (0., 0, 0);
+    for sample in samples {
+        let filtered = filters[channel].process(sample as f64);
+        sum += filtered * filtered;
+        channel += 1;
+        if channel == channels {
+            channel = 0;
+            frames += 1;
+            if frames == step {
+                steps.push(sum / step as f64);
+                sum = 0.;
+                frames = 0;
+            }
+        }
+    }
+
+    let loudness = |energy: f64| -0.691 + 10. * energy.log10();
+    let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+    let blocks = steps
+        .windows(4)
+        .map(mean)
+        .filter(|x| loudness(*x) > -70.)
+        .collect::<Vec<_>>();
+    if blocks.is_empty() {
+        return None;
+    }
+    let threshold = loudness(mean(&blocks)) - 10.;
+    let blocks = blocks
+        .into_iter()
+        .filter(|x| loudness(*x) > threshold)
+        .collect::<Vec<_>>();
+    Some(loudness(mean(&blocks)))
+}
+
+/// Direct form I biquad
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The K-weighting of BS.1770: a high shelf followed by a high pass,
+/// computed for any sample rate like libebur128 does
+#[derive(Clone)]
+struct KWeighting(Biquad, Biquad);
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        let shelf = Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            x: [0.; 2],
+            y: [0.; 2],
+        };
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1. + k / q + k * k;
+        let high_pass = Biquad {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            x: [0.; 2],
+            y: [0.; 2],
+        };
+        Self(shelf, high_pass)
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.1.process(self.0.process(x))
+    }
+}
@@ -1 +1,6 @@
+pub mod control;
+pub mod download;
 pub mod logger;
+pub mod loudness;
+pub mod mpd;
+pub mod player;
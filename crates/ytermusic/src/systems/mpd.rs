@@ -0,0 +1,677 @@
+use std::{
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    net::SocketAddr,
+    ops::Range,
+    time::Duration,
+};
+
+use flume::Sender;
+use log::{info, warn};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        TcpListener, TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    select,
+};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::{
+    protocol::{Command, Response, Status},
+    run_service,
+    systems::control::Request,
+};
+
+/// The version of the protocol, the clients check it for the commands they can send
+const GREETING: &str = "OK MPD 0.23.0\n";
+
+/// How often `idle` looks for changes
+const IDLE_INTERVAL: Duration = Duration::from_millis(500);
+
+const ACK_ERROR_ARG: u8 = 2;
+const ACK_ERROR_UNKNOWN: u8 = 5;
+const ACK_ERROR_NO_EXIST: u8 = 50;
+const ACK_ERROR_SYSTEM: u8 = 52;
+
+/// Listed by `commands`
+const COMMANDS: [&str; 27] = [
+    "add",
+    "clear",
+    "close",
+    "command_list_begin",
+    "command_list_end",
+    "command_list_ok_begin",
+    "commands",
+    "currentsong",
+    "find",
+    "getvol",
+    "idle",
+    "next",
+    "noidle",
+    "notcommands",
+    "pause",
+    "ping",
+    "play",
+    "playid",
+    "playlistid",
+    "playlistinfo",
+    "plchanges",
+    "previous",
+    "search",
+    "seekcur",
+    "setvol",
+    "status",
+    "tagtypes",
+];
+
+/// Commands answered without output, the clients send them at startup
+const IGNORED: [&str; 7] = [
+    "channels",
+    "decoders",
+    "listplaylists",
+    "outputs",
+    "password",
+    "readmessages",
+    "urlhandlers",
+];
+
+const SUBSYSTEMS: [&str; 4] = ["player", "mixer", "options", "playlist"];
+
+/// The state of each subsystem, `idle` reports the ones that differ
+type Snapshot = [(&'static str, String); 4];
+
+/// Serves a subset of the MPD protocol, the commands are answered by the receiver of the requests
+pub async fn spawn_mpd_system(address: SocketAddr, requests: Sender<Request>) -> io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("MPD server listening on {address}");
+
+    run_service(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    run_service(Client::new(stream, requests.clone()).run());
+                }
+                Err(e) => warn!("Can't accept an MPD connection: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// An error sent as `ACK [code@index] {command} message`
+struct Ack {
+    code: u8,
+    message: String,
+}
+
+impl Ack {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn argument(message: impl Into<String>) -> Self {
+        Self::new(ACK_ERROR_ARG, message)
+    }
+
+    /// `index` is the position of the command in the command list
+    fn format(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{index}] {{{command}}} {}\n",
+            self.code, self.message
+        )
+    }
+}
+
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    requests: Sender<Request>,
+    /// State at the connection or at the last `idle`, the next `idle` reports the changes since
+    seen: Option<Snapshot>,
+}
+
+impl Client {
+    fn new(stream: TcpStream, requests: Sender<Request>) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            requests,
+            seen: None,
+        }
+    }
+
+    /// Answers the commands until the client disconnects or sends `close`
+    async fn run(mut self) {
+        if self.writer.write_all(GREETING.as_bytes()).await.is_err() {
+            return;
+        }
+        // The first `idle` reports the changes made since the connection
+        self.seen = self.snapshot().await.ok();
+        while let Ok(Some(line)) = self.lines.next_line().await {
+            let Some(output) = self.handle_line(&line).await else {
+                break;
+            };
+            if self.writer.write_all(output.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// `None` closes the connection
+    async fn handle_line(&mut self, line: &str) -> Option<String> {
+        let list_ok = match line.trim() {
+            "command_list_begin" => false,
+            "command_list_ok_begin" => true,
+            _ => {
+                let arguments = match parse_arguments(line) {
+                    Ok(arguments) => arguments,
+                    Err(ack) => return Some(ack.format(0, "")),
+                };
+                return match self.execute(&arguments).await {
+                    Ok(output) => output.map(|x| x + "OK\n"),
+                    Err(ack) => Some(ack.format(0, command_name(&arguments))),
+                };
+            }
+        };
+        let mut lines = Vec::new();
+        loop {
+            let line = self.lines.next_line().await.ok()??;
+            if line.trim() == "command_list_end" {
+                break;
+            }
+            lines.push(line);
+        }
+        // The first error stops the list, the commands before it are kept
+        let mut output = String::new();
+        for (index, line) in lines.iter().enumerate() {
+            let result = match parse_arguments(line) {
+                Ok(arguments) => self
+                    .execute(&arguments)
+                    .await
+                    .map_err(|ack| ack.format(index, command_name(&arguments))),
+                Err(ack) => Err(ack.format(index, "")),
+            };
+            match result {
+                Ok(Some(text)) => output += &text,
+                Ok(None) => return None,
+                Err(ack) => return Some(output + &ack),
+            }
+            if list_ok {
+                output += "list_OK\n";
+            }
+        }
+        Some(output + "OK\n")
+    }
+
+    /// Gives the output of the command before its `OK`, `None` closes the connection
+    async fn execute(&mut self, arguments: &[String]) -> Result<Option<String>, Ack> {
+        let arguments = arguments.iter().map(String::as_str).collect::<Vec<_>>();
+        let [name, rest @ ..] = arguments.as_slice() else {
+            return Err(Ack::new(ACK_ERROR_UNKNOWN, "No command given"));
+        };
+        let mut output = String::new();
+        match (*name, rest) {
+            ("close", _) => return Ok(None),
+            ("ping" | "noidle", []) => (),
+            ("idle", subsystems) => return self.idle(subsystems).await,
+            ("commands", []) => {
+                for command in COMMANDS {
+                    field(&mut output, "command", command);
+                }
+            }
+            ("notcommands", []) => (),
+            ("tagtypes", []) => {
+                for tag in ["Artist", "Album", "Title"] {
+                    field(&mut output, "tagtype", tag);
+                }
+            }
+            // The tags of the songs are always the same
+            ("tagtypes", _) => (),
+            (name, _) if IGNORED.contains(&name) => (),
+            ("status", []) => {
+                let status = self.status().await?;
+                let queue = self.queue().await?;
+                write_status(&mut output, &status, &queue);
+            }
+            ("currentsong", []) => {
+                let status = self.status().await?;
+                let queue = self.queue().await?;
+                if let Some(video) = &status.video {
+                    let entry = status.index.and_then(|x| Some((x, *queue.ids.get(x)?)));
+                    write_song(&mut output, video, entry);
+                }
+            }
+            ("playlistinfo" | "playlistid", []) | ("plchanges", [_]) => {
+                let queue = self.queue().await?;
+                for (index, (video, id)) in queue.videos.iter().zip(&queue.ids).enumerate() {
+                    write_song(&mut output, video, Some((index, *id)));
+                }
+            }
+            ("playlistinfo", [range]) => {
+                let queue = self.queue().await?;
+                for index in parse_range(range, queue.videos.len())? {
+                    let entry = Some((index, queue.ids[index]));
+                    write_song(&mut output, &queue.videos[index], entry);
+                }
+            }
+            ("playlistid", [id]) => {
+                let queue = self.queue().await?;
+                let index = queue.position(parse_number(id)?)?;
+                let entry = Some((index, queue.ids[index]));
+                write_song(&mut output, &queue.videos[index], entry);
+            }
+            ("play" | "playid", []) => self.done(Command::Play { video_id: None }).await?,
+            ("play", [index]) => {
+                let index = parse_number(index)?;
+                self.done(Command::Select { index }).await?
+            }
+            ("playid", [id]) => {
+                let index = self.queue().await?.position(parse_number(id)?)?;
+                self.done(Command::Select { index }).await?
+            }
+            ("pause", []) => self.done(Command::Toggle).await?,
+            ("pause", ["1"]) => self.done(Command::Pause).await?,
+            ("pause", ["0"]) => self.done(Command::Play { video_id: None }).await?,
+            ("next", []) => self.done(Command::Next).await?,
+            ("previous", []) => self.done(Command::Previous).await?,
+            ("seekcur", [time]) => {
+                let relative = time.starts_with(['+', '-']);
+                let seconds = parse_number(time.trim_start_matches('+'))?;
+                self.done(Command::Seek { seconds, relative }).await?
+            }
+            ("setvol", [volume]) => {
+                let percent = parse_number(volume)?;
+                if !(0..=100).contains(&percent) {
+                    return Err(Ack::argument("Invalid volume value"));
+                }
+                let relative = false;
+                self.done(Command::Volume { percent, relative }).await?
+            }
+            ("getvol", []) => field(&mut output, "volume", self.status().await?.volume),
+            ("add", [uri]) => {
+                let video_ids = vec![parse_video_id(uri)];
+                self.done(Command::Enqueue { video_ids }).await?
+            }
+            ("clear", []) => self.done(Command::Clear).await?,
+            // Not in the subset, it finds the videos that `add` accepts
+            ("search" | "find", [_, _, ..]) => {
+                let query = rest.iter().skip(1).step_by(2).copied();
+                let query = query.collect::<Vec<_>>().join(" ");
+                match self.request(Command::Search { query }).await? {
+                    Response::Videos { videos } => {
+                        for video in &videos {
+                            write_song(&mut output, video, None);
+                        }
+                    }
+                    response => return Err(unexpected(response)),
+                }
+            }
+            (name, _) if COMMANDS.contains(&name) => {
+                return Err(Ack::argument("Wrong number of arguments"));
+            }
+            (name, _) => {
+                return Err(Ack::new(
+                    ACK_ERROR_UNKNOWN,
+                    format!("unknown command \"{name}\""),
+                ));
+            }
+        }
+        Ok(Some(output))
+    }
+
+    /// Waits for a change of the subsystems, or of all of them when none is given
+    async fn idle(&mut self, subsystems: &[&str]) -> Result<Option<String>, Ack> {
+        if let Some(subsystem) = subsystems.iter().find(|x| !SUBSYSTEMS.contains(x)) {
+            return Err(Ack::argument(format!(
+                "Unrecognized idle event: {subsystem}"
+            )));
+        }
+        let mut seen = match self.seen.take() {
+            Some(seen) => seen,
+            None => self.snapshot().await?,
+        };
+        let mut interval = tokio::time::interval(IDLE_INTERVAL);
+        loop {
+            select! {
+                _ = interval.tick() => (),
+                line = self.lines.next_line() => {
+                    self.seen = Some(seen);
+                    return Ok(match line {
+                        Ok(Some(line)) if line.trim() == "noidle" => Some(String::new()),
+                        // Only `noidle` can be sent while idle
+                        _ => None,
+                    });
+                }
+            }
+            let snapshot = self.snapshot().await?;
+            let mut output = String::new();
+            // The changes of the other subsystems are kept for the next `idle`
+            for ((name, old), (_, new)) in seen.iter_mut().zip(snapshot) {
+                if *old != new && (subsystems.is_empty() || subsystems.contains(name)) {
+                    field(&mut output, "changed", name);
+                    *old = new;
+                }
+            }
+            if !output.is_empty() {
+                self.seen = Some(seen);
+                return Ok(Some(output));
+            }
+        }
+    }
+
+    async fn snapshot(&self) -> Result<Snapshot, Ack> {
+        let status = self.status().await?;
+        let queue = self.queue().await?;
+        let video_id = status.video.map(|x| x.video_id);
+        Ok([
+            (
+                "player",
+                format!("{video_id:?} {} {:?}", status.paused, status.index),
+            ),
+            ("mixer", status.volume.to_string()),
+            ("options", format!("{} {}", status.repeat, status.shuffle)),
+            ("playlist", queue.version().to_string()),
+        ])
+    }
+
+    async fn request(&self, command: Command) -> Result<Response, Ack> {
+        let stopping = || Ack::new(ACK_ERROR_SYSTEM, "The player is stopping");
+        let (response_s, response_r) = flume::bounded(1);
+        self.requests
+            .send_async((command, response_s))
+            .await
+            .map_err(|_| stopping())?;
+        match response_r.recv_async().await {
+            Ok(Response::Error { message }) => Err(Ack::new(ACK_ERROR_NO_EXIST, message)),
+            Ok(response) => Ok(response),
+            Err(_) => Err(stopping()),
+        }
+    }
+
+    /// For the commands answered with `done`
+    async fn done(&self, command: Command) -> Result<(), Ack> {
+        match self.request(command).await? {
+            Response::Done => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn status(&self) -> Result<Status, Ack> {
+        match self.request(Command::Status).await? {
+            Response::Status(status) => Ok(status),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn queue(&self) -> Result<QueueEntries, Ack> {
+        match self.request(Command::Queue).await? {
+            Response::Queue { videos, ids, .. } => Ok(QueueEntries { videos, ids }),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+/// The videos of the queue with their ids, the `Id` of the songs
+struct QueueEntries {
+    videos: Vec<YoutubeMusicVideoRef>,
+    ids: Vec<u32>,
+}
+
+impl QueueEntries {
+    fn position(&self, id: u32) -> Result<usize, Ack> {
+        self.ids
+            .iter()
+            .position(|x| *x == id)
+            .ok_or_else(|| Ack::new(ACK_ERROR_NO_EXIST, "No such song"))
+    }
+
+    /// Changes when the videos of the queue change
+    fn version(&self) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        for (video, id) in self.videos.iter().zip(&self.ids) {
+            video.video_id.hash(&mut hasher);
+            id.hash(&mut hasher);
+        }
+        hasher.finish() as u32
+    }
+}
+
+fn unexpected(response: Response) -> Ack {
+    Ack::new(
+        ACK_ERROR_SYSTEM,
+        format!("Unexpected response: {response:?}"),
+    )
+}
+
+fn command_name(arguments: &[String]) -> &str {
+    arguments.first().map_or("", String::as_str)
+}
+
+/// Splits a command line, the arguments with spaces are quoted with `\` escaping the quotes
+fn parse_arguments(line: &str) -> Result<Vec<String>, Ack> {
+    let mut arguments = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut argument = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => argument.extend(chars.next()),
+                    Some(c) => argument.push(c),
+                    None => return Err(Ack::argument("Missing closing '\"'")),
+                }
+            }
+        } else {
+            argument.push(c);
+            while let Some(c) = chars.next_if(|x| !x.is_whitespace()) {
+                argument.push(c);
+            }
+        }
+        arguments.push(argument);
+    }
+    Ok(arguments)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, Ack> {
+    value
+        .parse()
+        .map_err(|_| Ack::argument(format!("Not a number: \"{value}\"")))
+}
+
+/// A position or a range such as `2:5`, the end of an open range is the end of the queue
+fn parse_range(value: &str, length: usize) -> Result<Range<usize>, Ack> {
+    let range = match value.split_once(':') {
+        Some((start, "")) => parse_number(start)?..length,
+        Some((start, end)) => parse_number(start)?..parse_number(end)?,
+        None => {
+            let index = parse_number(value)?;
+            index..index + 1
+        }
+    };
+    if range.start > range.end || range.end > length {
+        return Err(Ack::argument("Bad song index"));
+    }
+    Ok(range)
+}
+
+/// Accepts a video id, a `watch?v=` link or a `youtu.be/` short link
+fn parse_video_id(uri: &str) -> String {
+    let id = if let Some((_, query)) = uri.split_once("v=") {
+        query.split(['&', '#']).next()
+    } else if let Some((_, path)) = uri.split_once("youtu.be/") {
+        path.split(['?', '/', '#']).next()
+    } else {
+        None
+    };
+    id.unwrap_or(uri).to_string()
+}
+
+/// Durations such as `3:25` or `1:02:03` in seconds
+fn parse_duration(duration: &str) -> Option<u64> {
+    duration
+        .split(':')
+        .try_fold(0, |total, x| Some(total * 60 + x.parse::<u64>().ok()?))
+}
+
+fn field(output: &mut String, name: &str, value: impl Display) {
+    // A line break would end the value
+    let value = value.to_string().replace('\n', " ");
+    output.push_str(&format!("{name}: {value}\n"));
+}
+
+fn write_status(output: &mut String, status: &Status, queue: &QueueEntries) {
+    field(output, "volume", status.volume);
+    field(output, "repeat", u8::from(status.repeat != "off"));
+    field(output, "random", u8::from(status.shuffle));
+    field(output, "single", u8::from(status.repeat == "one"));
+    field(output, "consume", 0);
+    field(output, "playlist", queue.version());
+    field(output, "playlistlength", queue.videos.len());
+    let state = match (&status.video, status.paused) {
+        (None, _) => "stop",
+        (Some(_), true) => "pause",
+        (Some(_), false) => "play",
+    };
+    field(output, "state", state);
+    if let Some(index) = status.index {
+        field(output, "song", index);
+        if let Some(id) = queue.ids.get(index) {
+            field(output, "songid", id);
+        }
+    }
+    if status.video.is_some() {
+        let duration = status.duration.unwrap_or_default();
+        let time = format!("{}:{}", status.position as u64, duration as u64);
+        field(output, "time", time);
+        field(output, "elapsed", format!("{:.3}", status.position));
+        field(output, "duration", format!("{duration:.3}"));
+    }
+}
+
+/// The file of a song is its video id, `add` accepts it back.
+/// `entry` is the position and the id of the song in the queue.
+fn write_song(output: &mut String, video: &YoutubeMusicVideoRef, entry: Option<(usize, u32)>) {
+    field(output, "file", &video.video_id);
+    field(output, "Title", &video.title);
+    field(output, "Artist", &video.author);
+    if !video.album.is_empty() {
+        field(output, "Album", &video.album);
+    }
+    if let Some(seconds) = parse_duration(&video.duration) {
+        field(output, "Time", seconds);
+        field(output, "duration", seconds);
+    }
+    if let Some((index, id)) = entry {
+        field(output, "Pos", index);
+        field(output, "Id", id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_arguments() {
+        let arguments = parse_arguments(r#"  add "a b" c  "say \"hi\" \\o/" "" "#).ok();
+        assert_eq!(
+            arguments,
+            Some(
+                ["add", "a b", "c", r#"say "hi" \o/"#, ""]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+        assert_eq!(parse_arguments("").ok(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_missing_quote() {
+        let ack = parse_arguments(r#"add "a b"#).err().unwrap();
+        assert_eq!(ack.code, ACK_ERROR_ARG);
+        assert_eq!(ack.message, "Missing closing '\"'");
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("2", 5).ok(), Some(2..3));
+        assert_eq!(parse_range("2:", 5).ok(), Some(2..5));
+        assert_eq!(parse_range("1:3", 5).ok(), Some(1..3));
+        assert_eq!(parse_range("0:5", 5).ok(), Some(0..5));
+        for value in ["5", "6:", "2:6", "3:2", "a", "1:b"] {
+            let ack = parse_range(value, 5).err().unwrap();
+            assert_eq!(ack.code, ACK_ERROR_ARG, "{value}");
+        }
+        assert_eq!(parse_range("5", 5).err().unwrap().message, "Bad song index");
+    }
+
+    #[test]
+    fn parses_video_ids() {
+        for uri in [
+            "dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RDAMVM",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=10",
+            "https://youtu.be/dQw4w9WgXcQ?si=abcdef",
+            "youtu.be/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(parse_video_id(uri), "dQw4w9WgXcQ", "{uri}");
+        }
+    }
+
+    /// Sends `input` to a client and gives its answer to the first line
+    fn answer(input: &str) -> Option<String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut stream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (server, _) = listener.accept().await.unwrap();
+            let (requests, _requests_r) = flume::unbounded();
+            let mut client = Client::new(server, requests);
+            stream.write_all(input.as_bytes()).await.unwrap();
+            let first = client.lines.next_line().await.unwrap().unwrap();
+            client.handle_line(&first).await
+        })
+    }
+
+    #[test]
+    fn numbers_errors_in_command_lists() {
+        assert_eq!(
+            answer("command_list_ok_begin\nping\nping\nfoo 1\nping\ncommand_list_end\n").as_deref(),
+            Some("list_OK\nlist_OK\nACK [5@2] {foo} unknown command \"foo\"\n")
+        );
+        assert_eq!(
+            answer("command_list_begin\nping\n\"ping\ncommand_list_end\n").as_deref(),
+            Some("ACK [2@1] {} Missing closing '\"'\n")
+        );
+        assert_eq!(
+            answer("command_list_ok_begin\nping\nping\ncommand_list_end\n").as_deref(),
+            Some("list_OK\nlist_OK\nOK\n")
+        );
+    }
+
+    #[test]
+    fn numbers_single_errors_at_zero() {
+        assert_eq!(
+            answer("ping 1\n").as_deref(),
+            Some("ACK [2@0] {ping} Wrong number of arguments\n")
+        );
+        assert_eq!(answer("ping\n").as_deref(), Some("OK\n"));
+        assert_eq!(answer("close\n"), None);
+    }
+}
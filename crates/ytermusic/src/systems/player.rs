@@ -0,0 +1,590 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use flume::{Receiver, RecvTimeoutError, Sender};
+use log::{error, info, warn};
+use rodio::{
+    Decoder, OutputStream, OutputStreamBuilder, Sink, Source,
+    mixer::Mixer,
+    source::{Amplify, SeekError},
+};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::{
+    config::get_config,
+    errors::{ErrorCategory, handle_error, report_error},
+    shutdown::{guard_shutdown, is_shutdown_sent},
+    structures::{
+        queue::{Queue, QueueAction},
+        session::Session,
+    },
+    systems::{
+        download::{download, get_download_path},
+        loudness::Normalization,
+    },
+    term::ManagerMessage,
+};
+
+/// Interval between two position updates
+const TICK: Duration = Duration::from_millis(250);
+/// Interval between two volume changes during a crossfade
+const FADE_TICK: Duration = Duration::from_millis(20);
+/// Time before the end of a track when the next one is opened
+const PRELOAD: Duration = Duration::from_secs(10);
+/// Audio decoded when a track is opened, so it starts as soon as the previous one ends
+const PREFETCH: Duration = Duration::from_secs(2);
+pub const MAX_CROSSFADE: Duration = Duration::from_secs(12);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerAction {
+    /// Appends videos to the queue, playback starts if nothing is playing
+    Queue(Vec<YoutubeMusicVideoRef>),
+    /// Inserts a video after the current one and plays it right away
+    Play(YoutubeMusicVideoRef),
+    Edit(QueueAction),
+    PlayPause,
+    Forward,
+    Backward,
+    /// Moves to a position in the current video, clamped to its duration
+    Seek(Duration),
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    /// Percentage, clamped to 100
+    SetVolume(u8),
+    /// Length of the crossfade between two tracks, clamped to `MAX_CROSSFADE`.
+    /// Zero plays the tracks back to back.
+    SetCrossfade(Duration),
+    /// Applied from the next track on
+    SetNormalization(Normalization),
+    /// Replaces the queue and volume by the ones of the last run, paused where it stopped
+    Restore(Box<Session>),
+    /// Opens the default audio device again, the current track continues where it was
+    ReopenDevice,
+}
+
+/// Published on the updater channel at every tick of the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+    pub video: Option<YoutubeMusicVideoRef>,
+    /// The video is selected but its audio is not downloaded yet
+    pub loading: bool,
+    pub paused: bool,
+    pub position: Duration,
+    pub duration: Option<Duration>,
+    /// Percentage between 0 and 100
+    pub volume: u8,
+}
+
+/// Starts the player thread, the audio output is not `Send` so it lives on its own thread
+pub fn spawn_player_system(updater: Sender<ManagerMessage>) -> Sender<PlayerAction> {
+    let (action_s, action_r) = flume::unbounded();
+    // The session is saved when the player stops
+    let guard = guard_shutdown();
+    std::thread::spawn(move || {
+        Player::new(updater).run(action_r);
+        drop(guard);
+    });
+    action_s
+}
+
+/// A track appended to the sink after the current one, it plays without any gap
+struct Upcoming {
+    index: usize,
+    video_id: String,
+    duration: Option<Duration>,
+}
+
+struct Player {
+    updater: Sender<ManagerMessage>,
+    mixer: Mixer,
+    sink: Sink,
+    /// Sink of the previous track while it fades out
+    fading: Option<Sink>,
+    /// Dropping the stream stops the sound, `None` when playing in the null sink
+    _stream: Option<OutputStream>,
+    queue: Queue,
+    loaded: bool,
+    duration: Option<Duration>,
+    /// Position to seek to once the current track is loaded
+    resume_at: Option<Duration>,
+    /// The current track stays paused once loaded
+    start_paused: bool,
+    upcoming: Option<Upcoming>,
+    /// Next track that couldn't be opened, it is skipped when the current one ends
+    unplayable: Option<usize>,
+    volume: u8,
+    crossfade: Duration,
+    normalization: Normalization,
+    last_publish: Instant,
+}
+
+impl Player {
+    fn new(updater: Sender<ManagerMessage>) -> Self {
+        let (mixer, stream) = open_output(&updater);
+        Self {
+            updater,
+            sink: Sink::connect_new(&mixer),
+            mixer,
+            fading: None,
+            _stream: stream,
+            queue: Queue::default(),
+            loaded: false,
+            duration: None,
+            resume_at: None,
+            start_paused: false,
+            upcoming: None,
+            unplayable: None,
+            volume: 50,
+            crossfade: Duration::ZERO,
+            normalization: Normalization::default(),
+            last_publish: Instant::now(),
+        }
+    }
+
+    fn run(mut self, actions: Receiver<PlayerAction>) {
+        self.apply_volume();
+        // A restored session replaces it
+        self.edit(QueueAction::SetRepeat(get_config().player.repeat));
+        while !is_shutdown_sent() {
+            let timeout = if self.fading.is_some() {
+                FADE_TICK
+            } else {
+                TICK
+            };
+            match actions.recv_timeout(timeout) {
+                Ok(action) => self.apply(action),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            self.update();
+            if self.last_publish.elapsed() >= TICK {
+                self.publish();
+            }
+        }
+        let position = self.resume_at.unwrap_or_else(|| self.sink.get_pos());
+        Session::new(self.queue.clone(), position, self.volume).save();
+        info!("Player stopped");
+    }
+
+    fn apply(&mut self, action: PlayerAction) {
+        match action {
+            PlayerAction::Queue(videos) => {
+                let first = self.queue.videos().len();
+                self.change(QueueAction::Append(videos));
+                if self.queue.current_index().is_none() && first < self.queue.videos().len() {
+                    self.change(QueueAction::Select(first));
+                }
+            }
+            PlayerAction::Play(video) => {
+                let index = self.queue.current_index().map_or(0, |x| x + 1);
+                self.change(QueueAction::InsertNext(vec![video]));
+                self.change(QueueAction::Select(index));
+            }
+            PlayerAction::Edit(action) => self.change(action),
+            PlayerAction::PlayPause => {
+                let sinks = std::iter::once(&self.sink).chain(&self.fading);
+                if self.sink.is_paused() {
+                    sinks.for_each(Sink::play);
+                } else {
+                    sinks.for_each(Sink::pause);
+                }
+            }
+            PlayerAction::Forward => {
+                self.seek(self.sink.get_pos() + get_config().player.seek_step);
+            }
+            PlayerAction::Backward => {
+                let position = self.sink.get_pos();
+                self.seek(position.saturating_sub(get_config().player.seek_step))
+            }
+            PlayerAction::Seek(position) => self.seek(position),
+            PlayerAction::Next => {
+                if let Some(index) = self.queue.skip_index() {
+                    self.change(QueueAction::Select(index));
+                }
+            }
+            PlayerAction::Previous => {
+                if let Some(index) = self.queue.previous_index() {
+                    self.change(QueueAction::Select(index));
+                }
+            }
+            PlayerAction::VolumeUp => {
+                self.set_volume(self.volume.saturating_add(get_config().player.volume_step))
+            }
+            PlayerAction::VolumeDown => {
+                self.set_volume(self.volume.saturating_sub(get_config().player.volume_step))
+            }
+            PlayerAction::SetVolume(volume) => self.set_volume(volume),
+            PlayerAction::SetCrossfade(crossfade) => self.crossfade = crossfade.min(MAX_CROSSFADE),
+            PlayerAction::SetNormalization(normalization) => self.normalization = normalization,
+            PlayerAction::Restore(session) => {
+                self.set_volume(session.volume);
+                self.change(QueueAction::Restore(Box::new(session.queue)));
+                self.resume_at = Some(session.position);
+                self.start_paused = true;
+            }
+            PlayerAction::ReopenDevice => {
+                let position = self.resume_at.unwrap_or_else(|| self.sink.get_pos());
+                let paused = self.sink.is_paused();
+                // The previous stream is closed before the device is opened again
+                self._stream = None;
+                let (mixer, stream) = open_output(&self.updater);
+                self.sink = Sink::connect_new(&mixer);
+                self.mixer = mixer;
+                self._stream = stream;
+                self.start_current();
+                self.resume_at = Some(position);
+                self.start_paused = paused;
+            }
+        }
+    }
+
+    /// Applies a change of the queue and sends it to the manager,
+    /// returns `true` when the current video changed
+    fn edit(&mut self, action: QueueAction) -> bool {
+        let changed = self.queue.apply(action.clone());
+        self.updater
+            .send(ManagerMessage::Queue(action, Box::new(self.queue.clone())))
+            .unwrap();
+        changed
+    }
+
+    /// Applies a change of the queue asked by the user
+    fn change(&mut self, action: QueueAction) {
+        if self.edit(action) {
+            self.start_current();
+        } else {
+            self.check_upcoming();
+        }
+    }
+
+    fn seek(&mut self, position: Duration) {
+        let position = self.duration.map_or(position, |x| position.min(x));
+        // The seek moves away from the end of the previous track
+        self.fading = None;
+        self.apply_volume();
+        if let Err(e) = self.sink.try_seek(position) {
+            warn!("Can't seek to {position:?}: {e}");
+        }
+    }
+
+    fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100);
+        self.apply_volume();
+    }
+
+    /// Sets the volume of the sinks, the crossfade progresses with the position of the new track
+    fn apply_volume(&self) {
+        let volume = self.volume as f32 / 100.;
+        match &self.fading {
+            Some(fading) => {
+                let progress = if self.crossfade.is_zero() {
+                    1.
+                } else {
+                    (self.sink.get_pos().as_secs_f32() / self.crossfade.as_secs_f32()).min(1.)
+                };
+                self.sink.set_volume(volume * progress);
+                fading.set_volume(volume * (1. - progress));
+            }
+            None => self.sink.set_volume(volume),
+        }
+    }
+
+    /// Stops the sound and starts the current video of the queue, it is downloaded first if needed
+    fn start_current(&mut self) {
+        self.sink.clear();
+        self.fading = None;
+        self.upcoming = None;
+        self.unplayable = None;
+        self.loaded = false;
+        self.duration = None;
+        self.resume_at = None;
+        self.start_paused = false;
+        self.apply_volume();
+        if let Some(video) = self.queue.current() {
+            download(video.clone(), &self.updater);
+        }
+    }
+
+    /// The track appended to the sink must still be the next one after a change of the queue,
+    /// otherwise the current track is reopened where it was
+    fn check_upcoming(&mut self) {
+        self.unplayable = None;
+        let Some(upcoming) = &mut self.upcoming else {
+            return;
+        };
+        match self.queue.next_index() {
+            Some(index) if self.queue.videos()[index].video_id == upcoming.video_id => {
+                upcoming.index = index;
+            }
+            _ => {
+                let position = self.sink.get_pos();
+                self.start_current();
+                self.resume_at = Some(position);
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let Some(current) = self.queue.current_index() else {
+            return;
+        };
+        if !self.loaded {
+            if let Some(path) = get_audio_file(&self.queue.videos()[current]) {
+                let gain = self
+                    .normalization
+                    .get_gain_db(&self.queue.videos()[current]);
+                match open_track(&path, gain) {
+                    Ok(track) => {
+                        self.duration = track.total_duration();
+                        self.sink.append(track);
+                        if !std::mem::take(&mut self.start_paused) {
+                            self.sink.play();
+                        }
+                        self.loaded = true;
+                        if let Some(position) = self.resume_at.take() {
+                            self.seek(position);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Can't play {}: {e}", self.queue.videos()[current].video_id);
+                        // Repeating an unplayable video would retry it forever
+                        match self.queue.skip_index().filter(|x| *x != current) {
+                            Some(index) => self.change(QueueAction::Select(index)),
+                            None => self.change(QueueAction::Stop),
+                        }
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Some(upcoming) = self.upcoming.take_if(|_| self.sink.len() < 2) {
+            // The sink moved to the upcoming track
+            self.edit(QueueAction::Select(upcoming.index));
+            self.duration = upcoming.duration;
+            return;
+        }
+        if self.sink.empty() {
+            match self.queue.next_index() {
+                Some(index) => self.change(QueueAction::Select(index)),
+                None => self.change(QueueAction::Stop),
+            }
+            return;
+        }
+        if let Some(fading) = &self.fading
+            && (fading.empty() || self.sink.get_pos() >= self.crossfade)
+        {
+            self.fading = None;
+        }
+        self.apply_volume();
+        self.prepare_next(current);
+    }
+
+    /// Opens the track after `current` when the end is near, it is either appended to the sink
+    /// or started in a new sink when the tracks crossfade
+    fn prepare_next(&mut self, current: usize) {
+        let Some(next) = self.queue.next_index() else {
+            return;
+        };
+        if self.upcoming.is_some() || self.fading.is_some() || self.unplayable == Some(next) {
+            return;
+        }
+        let Some(remaining) = self.duration.map(|x| x.saturating_sub(self.sink.get_pos())) else {
+            return;
+        };
+        if remaining > PRELOAD.max(self.crossfade) {
+            return;
+        }
+        let video = self.queue.videos()[next].clone();
+        download(video.clone(), &self.updater);
+
+        // Tracks of the same album are often meant to be played without interruption
+        let crossfade =
+            !self.crossfade.is_zero() && !is_same_album(&self.queue.videos()[current], &video);
+        if crossfade && remaining > self.crossfade {
+            return;
+        }
+        let Some(path) = get_audio_file(&video) else {
+            return;
+        };
+        let track = match open_track(&path, self.normalization.get_gain_db(&video)) {
+            Ok(track) => track,
+            Err(e) => {
+                error!("Can't open {}: {e}", video.video_id);
+                self.unplayable = Some(next);
+                return;
+            }
+        };
+        let duration = track.total_duration();
+        if crossfade {
+            let sink = Sink::connect_new(&self.mixer);
+            sink.append(track);
+            self.fading = Some(std::mem::replace(&mut self.sink, sink));
+            self.edit(QueueAction::Select(next));
+            self.duration = duration;
+            self.apply_volume();
+        } else {
+            self.sink.append(track);
+            self.upcoming = Some(Upcoming {
+                index: next,
+                video_id: video.video_id,
+                duration,
+            });
+        }
+    }
+
+    fn publish(&mut self) {
+        self.last_publish = Instant::now();
+        let state = PlayerState {
+            video: self.queue.current().cloned(),
+            loading: self.queue.current().is_some() && !self.loaded,
+            paused: self.sink.is_paused(),
+            position: self.sink.get_pos(),
+            duration: self.duration,
+            volume: self.volume,
+        };
+        self.updater
+            .send(ManagerMessage::PlayerState(state))
+            .unwrap();
+    }
+}
+
+fn is_same_album(a: &YoutubeMusicVideoRef, b: &YoutubeMusicVideoRef) -> bool {
+    !a.album.is_empty() && a.album == b.album
+}
+
+/// Returns the cached audio file of a video, downloads are mp4 but webm files are played too
+pub fn get_audio_file(video: &YoutubeMusicVideoRef) -> Option<PathBuf> {
+    let path = get_download_path(&video.video_id);
+    if !path.with_extension("json").exists() {
+        return None;
+    }
+    ["mp4", "webm"]
+        .into_iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|x| x.exists())
+}
+
+fn open_track(path: &Path, gain_db: f64) -> Result<Amplify<PrefetchedTrack>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let length = file.metadata().map_err(|e| e.to_string())?.len();
+    let decoder = Decoder::builder()
+        .with_data(BufReader::new(file))
+        .with_byte_len(length)
+        .with_seekable(true)
+        .with_hint(&path.extension().unwrap_or_default().to_string_lossy())
+        .build()
+        .map_err(|e| e.to_string())?;
+    Ok(PrefetchedTrack::new(decoder).amplify_decibel(gain_db as f32))
+}
+
+/// A decoded track whose first samples are decoded ahead of time
+struct PrefetchedTrack {
+    head: std::vec::IntoIter<f32>,
+    decoder: Decoder<BufReader<File>>,
+}
+
+impl PrefetchedTrack {
+    fn new(mut decoder: Decoder<BufReader<File>>) -> Self {
+        let samples = PREFETCH.as_secs() as usize
+            * decoder.sample_rate() as usize
+            * decoder.channels() as usize;
+        let head = decoder.by_ref().take(samples).collect::<Vec<_>>();
+        Self {
+            head: head.into_iter(),
+            decoder,
+        }
+    }
+}
+
+impl Iterator for PrefetchedTrack {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.head.next().or_else(|| self.decoder.next())
+    }
+}
+
+impl Source for PrefetchedTrack {
+    fn current_span_len(&self) -> Option<usize> {
+        // The head is played first, the span of the decoder only starts after it
+        if self.head.len() > 0 {
+            Some(self.head.len())
+        } else {
+            self.decoder.current_span_len()
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.decoder.total_duration()
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.head = Vec::new().into_iter();
+        self.decoder.try_seek(position)
+    }
+}
+
+/// Opens the default audio device, or a mixer played in real time without any output.
+/// The loss of the device is reported to the error screen.
+fn open_output(updater: &Sender<ManagerMessage>) -> (Mixer, Option<OutputStream>) {
+    let lost = updater.clone();
+    // The callback is called for every failed write, the loss is reported once
+    let reported = Arc::new(AtomicBool::new(false));
+    let stream = OutputStreamBuilder::from_default_device().and_then(|x| {
+        x.with_error_callback(move |e| match e {
+            rodio::cpal::StreamError::DeviceNotAvailable => {
+                if !reported.swap(true, Ordering::Relaxed) {
+                    report_error(
+                        &lost,
+                        ErrorCategory::AudioDevice,
+                        format!("The audio device was lost: {e}"),
+                    );
+                }
+            }
+            e => warn!("Audio stream error: {e}"),
+        })
+        .open_stream_or_fallback()
+    });
+    match stream {
+        Ok(mut stream) => {
+            stream.log_on_drop(false);
+            (stream.mixer().clone(), Some(stream))
+        }
+        Err(e) => {
+            warn!("No audio device available, using a null sink: {e}");
+            handle_error(
+                updater,
+                "No audio device available, using a null sink",
+                Err(e),
+            );
+            let (mixer, mut output) = rodio::mixer::mixer(2, 44_100);
+            std::thread::spawn(move || {
+                while !is_shutdown_sent() {
+                    let samples = output.sample_rate() as usize * output.channels() as usize / 10;
+                    output.by_ref().take(samples).for_each(drop);
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            });
+            (mixer, None)
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use flume::Sender;
+use log::{trace, warn};
+use ytapi2::{endpoint::Endpoint, instance::YoutubeMusicInstance};
+
+use crate::{
+    database::DATABASE, errors::handle_error_option, run_service, structures::perfomance,
+    term::ManagerMessage,
+};
+
+/// Playlists of the library fetched past the first page
+const LIBRARY_CONTINUATIONS: usize = 2;
+/// Pages of tracks fetched for each playlist
+const PLAYLIST_CONTINUATIONS: usize = 5;
+
+/// Fills the playlist chooser with the local musics and the playlists of the library
+pub fn spawn_library_task(instance: Arc<YoutubeMusicInstance>, updater: Sender<ManagerMessage>) {
+    run_service(async move {
+        let guard = perfomance::guard("Library Task");
+        updater
+            .send(ManagerMessage::AddElementToChooser((
+                "Local musics".to_string(),
+                DATABASE.videos(),
+            )))
+            .unwrap();
+
+        let Some(playlists) = handle_error_option(
+            &updater,
+            "Can't fetch the library",
+            instance
+                .get_library(&Endpoint::MusicLikedPlaylists, LIBRARY_CONTINUATIONS)
+                .await,
+        ) else {
+            return;
+        };
+        for playlist in playlists {
+            trace!("Fetching the playlist {}", playlist.name);
+            match instance
+                .get_playlist(&playlist.browse_id, PLAYLIST_CONTINUATIONS)
+                .await
+            {
+                Ok(content) => updater
+                    .send(ManagerMessage::AddElementToChooser((
+                        playlist.name,
+                        content.tracks,
+                    )))
+                    .unwrap(),
+                Err(e) => warn!("Can't fetch the playlist {}: {e:?}", playlist.name),
+            }
+        }
+        drop(guard);
+    });
+}
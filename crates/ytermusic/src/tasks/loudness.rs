@@ -0,0 +1,35 @@
+use log::{trace, warn};
+
+use crate::{
+    consts::CACHE_DIR, database::DATABASE, run_service, structures::perfomance,
+    systems::loudness::measure_file,
+};
+
+/// Measures the cached tracks for which the player endpoint gave no loudness
+pub fn spawn_loudness_task() {
+    run_service(async move {
+        let guard = perfomance::guard("Loudness Task");
+        for i in std::fs::read_dir(CACHE_DIR.join("downloads")).unwrap() {
+            let path = i.unwrap().path();
+            if path.extension().unwrap_or_default() != "mp4"
+                || !path.with_extension("json").exists()
+            {
+                continue;
+            }
+            let video_id = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if DATABASE.get_loudness(&video_id).is_some() {
+                continue;
+            }
+            trace!("Measuring the loudness of {video_id}");
+            match tokio::task::spawn_blocking(move || measure_file(&path)).await {
+                Ok(Some(lufs)) => DATABASE.set_loudness(&video_id, lufs),
+                _ => warn!("Can't measure the loudness of {video_id}"),
+            }
+        }
+        drop(guard);
+    });
+}
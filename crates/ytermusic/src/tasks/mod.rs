@@ -1 +1,3 @@
 pub mod clean;
+pub mod library;
+pub mod loudness;
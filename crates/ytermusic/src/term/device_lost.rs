@@ -0,0 +1,160 @@
+use ratatui::{
+    Frame,
+    crossterm::event::MouseEvent,
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{ListItem, Paragraph, Wrap},
+};
+
+use crate::{
+    config::get_config,
+    errors::ErrorCategory,
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector,
+    },
+};
+
+/// Errors kept in the history, the oldest ones are dropped first
+const HISTORY_LENGTH: usize = 100;
+
+struct ErrorEntry {
+    category: ErrorCategory,
+    message: String,
+    /// Dispatched by the action of the category
+    follow_up: Option<ManagerMessage>,
+    /// Times the same error was reported in a row
+    count: usize,
+}
+
+/// Shows the errors reported by the systems and offers the action that can fix each of them
+#[derive(Default)]
+pub struct DeviceLost {
+    errors: Vec<ErrorEntry>,
+    list: ListSelector,
+}
+
+impl DeviceLost {
+    fn selected(&self) -> Option<&ErrorEntry> {
+        self.errors.get(self.list.selected()?)
+    }
+
+    fn render_details(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let theme = &get_config().ui.theme;
+        let Some(entry) = self.selected() else {
+            frame.render_widget(
+                Paragraph::new("No error").block(theme.block().title(" Details ")),
+                area,
+            );
+            return;
+        };
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let mut lines = vec![
+            Line::from(Span::styled(entry.category.hint(), bold)),
+            Line::raw(""),
+            Line::raw(entry.message.as_str()),
+        ];
+        if entry.count > 1 {
+            lines.push(Line::raw(""));
+            lines.push(Line::raw(format!("Reported {} times", entry.count)));
+        }
+        let mut shortcuts = vec![(Action::ClearErrors, "clear"), (Action::Back, "back")];
+        if let (Some(_), Some(action)) = (&entry.follow_up, entry.category.action_name()) {
+            shortcuts.insert(0, (Action::Select, action));
+        }
+        let details = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            theme
+                .block()
+                .title(format!(" {} ", entry.category.name()))
+                .title_bottom(hints(&shortcuts)),
+        );
+        frame.render_widget(details, area);
+    }
+}
+
+impl Screen for DeviceLost {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        self.list.on_mouse_press(&event);
+        EventResponse::None
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back => {
+                EventResponse::message(ManagerMessage::ChangeState(Screens::MusicPlayer))
+            }
+            Action::Select => match self.selected().and_then(|x| x.follow_up.clone()) {
+                // A new error brings the screen back if the action fails
+                Some(message) => EventResponse::Message(vec![
+                    message,
+                    ManagerMessage::ChangeState(Screens::MusicPlayer),
+                ]),
+                None => EventResponse::None,
+            },
+            Action::ClearErrors => {
+                self.errors.clear();
+                self.list.update(0);
+                EventResponse::None
+            }
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [history, details] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(frame.area());
+        let theme = &get_config().ui.theme;
+        let items = self
+            .errors
+            .iter()
+            .map(|entry| {
+                let mut spans = vec![
+                    Span::styled(
+                        format!("[{}] ", entry.category.name()),
+                        Style::default().fg(theme.error),
+                    ),
+                    Span::raw(entry.message.lines().next().unwrap_or_default()),
+                ];
+                if entry.count > 1 {
+                    spans.push(Span::raw(format!(" (x{})", entry.count)));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let block = theme
+            .block()
+            .title(format!(" Errors ({}) ", self.errors.len()));
+        self.list.render(frame, history, block, items);
+        self.render_details(frame, details);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        let ManagerMessage::Error(category, message, follow_up) = message else {
+            return EventResponse::None;
+        };
+        if let Some(last) = self.errors.last_mut()
+            && last.category == category
+            && last.message == message
+        {
+            last.count += 1;
+        } else {
+            self.errors.push(ErrorEntry {
+                category,
+                message,
+                follow_up: *follow_up,
+                count: 1,
+            });
+            if self.errors.len() > HISTORY_LENGTH {
+                self.errors.remove(0);
+            }
+        }
+        self.list.update(self.errors.len());
+        self.list.select(self.errors.len() - 1);
+        EventResponse::message(ManagerMessage::ChangeState(Screens::DeviceLost))
+    }
+}
@@ -0,0 +1,91 @@
+use ratatui::{
+    Frame,
+    crossterm::event::MouseEvent,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::ListItem,
+};
+
+use crate::{
+    config::get_config,
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector,
+    },
+};
+
+/// Lists the shortcuts of the active keymap
+pub struct Help {
+    list: ListSelector,
+    /// Screen opened when leaving the help
+    previous: Screens,
+}
+
+impl Default for Help {
+    fn default() -> Self {
+        Self {
+            list: ListSelector::default(),
+            previous: Screens::MusicPlayer,
+        }
+    }
+}
+
+impl Screen for Help {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        self.list.on_mouse_press(&event);
+        EventResponse::None
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back => EventResponse::message(ManagerMessage::ChangeState(self.previous)),
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let config = get_config();
+        let keymap = &config.keymap;
+        let help = keymap.help();
+        let width = help
+            .iter()
+            .map(|(x, _)| x.chars().count())
+            .max()
+            .unwrap_or(0);
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let items = help
+            .into_iter()
+            .map(|(keys, description)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{keys:<width$}  "), bold),
+                    Span::raw(description),
+                ]))
+            })
+            .collect();
+        let block = config
+            .ui
+            .theme
+            .block()
+            .title(format!(" Shortcuts ({} keymap) ", keymap.name()))
+            .title_bottom(hints(&[(Action::Back, "back")]));
+        self.list.render(frame, frame.area(), block, items);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        match message {
+            // The help key closes the help
+            ManagerMessage::HelpFrom(Screens::Help) => {
+                return EventResponse::message(ManagerMessage::ChangeState(self.previous));
+            }
+            ManagerMessage::HelpFrom(screen) => {
+                self.previous = screen;
+                return EventResponse::message(ManagerMessage::ChangeState(Screens::Help));
+            }
+            _ => (),
+        }
+        EventResponse::None
+    }
+}
@@ -0,0 +1,551 @@
+use std::{fmt::Display, str::FromStr};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What a key does, the screens interpret the actions that make sense for them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    PlayPause,
+    Select,
+    Search,
+    Shuffle,
+    Repeat,
+    Remove,
+    /// Also opens the selected playlist
+    SeekForward,
+    /// Also leaves the opened playlist
+    SeekBackward,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Back,
+    ClearErrors,
+    Help,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 22] = [
+        Action::PlayPause,
+        Action::Select,
+        Action::Search,
+        Action::Shuffle,
+        Action::Repeat,
+        Action::Remove,
+        Action::SeekForward,
+        Action::SeekBackward,
+        Action::Next,
+        Action::Previous,
+        Action::VolumeUp,
+        Action::VolumeDown,
+        Action::Up,
+        Action::Down,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::Back,
+        Action::ClearErrors,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Name used in `config.toml`
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::PlayPause => "play-pause",
+            Action::Select => "select",
+            Action::Search => "search",
+            Action::Shuffle => "shuffle",
+            Action::Repeat => "repeat",
+            Action::Remove => "remove",
+            Action::SeekForward => "seek-forward",
+            Action::SeekBackward => "seek-backward",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::VolumeUp => "volume-up",
+            Action::VolumeDown => "volume-down",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::PageUp => "page-up",
+            Action::PageDown => "page-down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Back => "back",
+            Action::ClearErrors => "clear-errors",
+            Action::Help => "help",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::PlayPause => "play/pause",
+            Action::Select => "select a playlist or a music",
+            Action::Search => "search",
+            Action::Shuffle => "shuffle",
+            Action::Repeat => "change the repeat mode (off, all, one)",
+            Action::Remove => "remove a music from the main playlist",
+            Action::SeekForward => "go forward in the music, or open a playlist",
+            Action::SeekBackward => "go back in the music, or leave a playlist",
+            Action::Next => "go to the next song",
+            Action::Previous => "go to the previous song",
+            Action::VolumeUp => "volume up",
+            Action::VolumeDown => "volume down",
+            Action::Up => "scroll up",
+            Action::Down => "scroll down",
+            Action::PageUp => "scroll a page up",
+            Action::PageDown => "scroll a page down",
+            Action::Top => "go to the top of the list",
+            Action::Bottom => "go to the bottom of the list",
+            Action::Back => "exit the current menu",
+            Action::ClearErrors => "clear the errors",
+            Action::Help => "show the shortcuts",
+            Action::Quit => "quit",
+        }
+    }
+}
+
+const DEFAULT_PRESET: &[(Action, &[&str])] = &[
+    (Action::PlayPause, &["space"]),
+    (Action::Select, &["enter"]),
+    (Action::Search, &["f"]),
+    (Action::Shuffle, &["s"]),
+    (Action::Repeat, &["R"]),
+    (Action::Remove, &["r", "delete"]),
+    (Action::SeekForward, &["right", ">"]),
+    (Action::SeekBackward, &["left", "<"]),
+    (Action::Next, &["ctrl+right", "ctrl+>"]),
+    (Action::Previous, &["ctrl+left", "ctrl+<"]),
+    (Action::VolumeUp, &["+"]),
+    (Action::VolumeDown, &["-"]),
+    (Action::Up, &["up"]),
+    (Action::Down, &["down"]),
+    (Action::PageUp, &["pageup"]),
+    (Action::PageDown, &["pagedown"]),
+    (Action::Top, &["home"]),
+    (Action::Bottom, &["end"]),
+    (Action::Back, &["esc"]),
+    (Action::ClearErrors, &["c"]),
+    (Action::Help, &["?"]),
+    (Action::Quit, &["ctrl+c", "ctrl+d"]),
+];
+
+const VIM_PRESET: &[(Action, &[&str])] = &[
+    (Action::PlayPause, &["space"]),
+    (Action::Select, &["enter"]),
+    (Action::Search, &["/"]),
+    (Action::Shuffle, &["s"]),
+    (Action::Repeat, &["r"]),
+    (Action::Remove, &["d d", "delete"]),
+    (Action::SeekForward, &["l", "right"]),
+    (Action::SeekBackward, &["h", "left"]),
+    (Action::Next, &["L"]),
+    (Action::Previous, &["H"]),
+    (Action::VolumeUp, &["+", "="]),
+    (Action::VolumeDown, &["-"]),
+    (Action::Up, &["k", "up"]),
+    (Action::Down, &["j", "down"]),
+    (Action::PageUp, &["ctrl+u", "ctrl+b"]),
+    (Action::PageDown, &["ctrl+d", "ctrl+f"]),
+    (Action::Top, &["g g"]),
+    (Action::Bottom, &["G"]),
+    (Action::Back, &["esc", "q"]),
+    (Action::ClearErrors, &["c"]),
+    (Action::Help, &["?"]),
+    (Action::Quit, &["ctrl+c", ": q"]),
+];
+
+/// A key with its modifiers, shift is part of the character for the character keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Parses keys such as `a`, `R`, `+`, `ctrl+right` or `f5`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The last part is the key, which can be `+` itself
+        let (modifiers, key) = match s.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => {
+                (rest.strip_suffix('+').unwrap_or(rest), "+")
+            }
+            _ => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+        let mut parsed = KeyModifiers::NONE;
+        for modifier in modifiers.split('+').filter(|x| !x.is_empty()) {
+            parsed |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("`{modifier}` isn't a modifier in `{s}`")),
+            };
+        }
+        let lowercase = key.to_lowercase();
+        let code = if let Some((_, code)) = NAMED_KEYS.iter().find(|(x, _)| *x == lowercase) {
+            *code
+        } else if let Some(n) = lowercase.strip_prefix('f').and_then(|x| x.parse().ok()) {
+            KeyCode::F(n)
+        } else {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("`{key}` isn't a key in `{s}`")),
+            }
+        };
+        Ok(Self::new(code, parsed))
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match NAMED_KEYS.iter().find(|(_, x)| *x == self.code) {
+            Some((name, _)) => f.write_str(name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{c}"),
+                KeyCode::F(n) => write!(f, "f{n}"),
+                code => write!(f, "{code:?}"),
+            },
+        }
+    }
+}
+
+/// Keys pressed one after the other, such as `g g`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(Vec<Key>);
+
+impl KeySequence {
+    fn starts_with(&self, other: &[Key]) -> bool {
+        self.0.starts_with(other)
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys = s
+            .split_whitespace()
+            .map(Key::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err("A binding can't be empty".to_string());
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl Display for KeySequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self.0.iter().map(Key::to_string).collect::<Vec<_>>();
+        f.write_str(&keys.join(" "))
+    }
+}
+
+/// Bindings of the actions, no binding is equal to or starts with another one
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    name: &'static str,
+    bindings: Vec<(KeySequence, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_preset("default", DEFAULT_PRESET)
+    }
+}
+
+impl Keymap {
+    pub const PRESETS: [&str; 2] = ["default", "vim"];
+
+    fn from_preset(name: &'static str, preset: &[(Action, &[&str])]) -> Self {
+        let mut keymap = Self {
+            name,
+            bindings: Vec::new(),
+        };
+        for (action, sequences) in preset {
+            let sequences = sequences.iter().map(|x| x.parse().unwrap()).collect();
+            keymap.bind(*action, sequences).unwrap();
+        }
+        keymap
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "vim" => Some(Self::from_preset("vim", VIM_PRESET)),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.retain(|(_, x)| *x != action);
+    }
+
+    /// Adds bindings to an action, fails if one of them conflicts with another action
+    pub fn bind(&mut self, action: Action, sequences: Vec<KeySequence>) -> Result<(), String> {
+        for sequence in sequences {
+            if let Some((other, other_action)) = self
+                .bindings
+                .iter()
+                .find(|(x, _)| x.starts_with(&sequence.0) || sequence.starts_with(&x.0))
+            {
+                if *other_action == action && *other == sequence {
+                    continue;
+                }
+                return Err(format!(
+                    "`{sequence}` of {} conflicts with `{other}` of {}",
+                    action.name(),
+                    other_action.name()
+                ));
+            }
+            self.bindings.push((sequence, action));
+        }
+        Ok(())
+    }
+
+    pub fn get_bindings(&self, action: Action) -> impl Iterator<Item = &KeySequence> {
+        self.bindings
+            .iter()
+            .filter(move |(_, x)| *x == action)
+            .map(|(x, _)| x)
+    }
+
+    /// Adds a key to the pending sequence, returns the action once a binding is complete.
+    /// A key that continues no binding starts a new sequence.
+    pub fn resolve(&self, pending: &mut Vec<Key>, key: Key) -> Option<Action> {
+        pending.push(key);
+        if !self.continues(pending) {
+            pending.clear();
+            pending.push(key);
+        }
+        if let Some((_, action)) = self.bindings.iter().find(|(x, _)| x.0 == *pending) {
+            pending.clear();
+            return Some(*action);
+        }
+        if !self.continues(pending) {
+            pending.clear();
+        }
+        None
+    }
+
+    fn continues(&self, pending: &[Key]) -> bool {
+        self.bindings.iter().any(|(x, _)| x.starts_with(pending))
+    }
+
+    /// Hints shown at the bottom of the screens, with the first binding of each action
+    pub fn hints(&self, hints: &[(Action, &str)]) -> String {
+        let hints = hints
+            .iter()
+            .filter_map(|(action, label)| {
+                let keys = self.get_bindings(*action).next()?;
+                Some(format!("{keys}: {label}"))
+            })
+            .collect::<Vec<_>>();
+        format!(" {} ", hints.join(" | "))
+    }
+
+    /// Shortcuts of the actions with a binding, with their description
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        Action::ALL
+            .into_iter()
+            .filter_map(|action| {
+                let keys = self
+                    .get_bindings(action)
+                    .map(KeySequence::to_string)
+                    .collect::<Vec<_>>();
+                (!keys.is_empty()).then(|| (keys.join(" or "), action.description()))
+            })
+            .collect()
+    }
+
+    /// The shortcuts printed by `--help`
+    pub fn help_text(&self) -> String {
+        let mut text = format!("Shortcuts ({} keymap):\n", self.name);
+        text.push_str(
+            "        Use your mouse to click in lists if your terminal has mouse support\n",
+        );
+        for (keys, description) in self.help() {
+            text.push_str(&format!("        {keys:<26}{description}\n"));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> Key {
+        s.parse().unwrap()
+    }
+
+    fn sequences(s: &[&str]) -> Vec<KeySequence> {
+        s.iter().map(|x| x.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(key("a"), Key::new(KeyCode::Char('a'), KeyModifiers::NONE));
+        assert_eq!(key("R"), Key::new(KeyCode::Char('R'), KeyModifiers::NONE));
+        assert_eq!(key("shift+r"), key("R"));
+        assert_eq!(key("+"), Key::new(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(
+            key("ctrl++"),
+            Key::new(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            key("Ctrl+Alt+right"),
+            Key::new(KeyCode::Right, KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(
+            key("shift+tab"),
+            Key::new(KeyCode::Tab, KeyModifiers::SHIFT)
+        );
+        assert_eq!(
+            key("space"),
+            Key::new(KeyCode::Char(' '), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            key("PageDown"),
+            Key::new(KeyCode::PageDown, KeyModifiers::NONE)
+        );
+        assert_eq!(key("f5"), Key::new(KeyCode::F(5), KeyModifiers::NONE));
+        assert_eq!(key("f"), Key::new(KeyCode::Char('f'), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert_eq!(
+            "super+a".parse::<Key>(),
+            Err("`super` isn't a modifier in `super+a`".to_string())
+        );
+        assert_eq!(
+            "ctrl+foo".parse::<Key>(),
+            Err("`foo` isn't a key in `ctrl+foo`".to_string())
+        );
+        assert!(" ".parse::<KeySequence>().is_err());
+    }
+
+    #[test]
+    fn displays_keys() {
+        for s in [
+            "a",
+            "R",
+            "+",
+            "ctrl+right",
+            "ctrl+alt+q",
+            "space",
+            "f5",
+            "g g",
+        ] {
+            assert_eq!(s.parse::<KeySequence>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn rejects_prefixes() {
+        let mut keymap = Keymap::preset("vim").unwrap();
+        assert_eq!(
+            keymap.bind(Action::Search, sequences(&["g"])),
+            Err("`g` of search conflicts with `g g` of top".to_string())
+        );
+        assert_eq!(
+            keymap.bind(Action::Search, sequences(&["d d x"])),
+            Err("`d d x` of search conflicts with `d d` of remove".to_string())
+        );
+        // Binding the same sequence again to the same action isn't a conflict
+        assert_eq!(keymap.bind(Action::Top, sequences(&["g g"])), Ok(()));
+        assert_eq!(keymap.bind(Action::Search, sequences(&["g s"])), Ok(()));
+    }
+
+    #[test]
+    fn resolves_sequences() {
+        let keymap = Keymap::preset("vim").unwrap();
+        let mut pending = Vec::new();
+        assert_eq!(keymap.resolve(&mut pending, key("g")), None);
+        assert_eq!(pending, [key("g")]);
+        assert_eq!(keymap.resolve(&mut pending, key("g")), Some(Action::Top));
+        assert!(pending.is_empty());
+        // A key that doesn't continue the sequence starts a new one
+        assert_eq!(keymap.resolve(&mut pending, key("g")), None);
+        assert_eq!(keymap.resolve(&mut pending, key("j")), Some(Action::Down));
+        assert!(pending.is_empty());
+        assert_eq!(keymap.resolve(&mut pending, key("x")), None);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn loads_presets() {
+        for name in Keymap::PRESETS {
+            let keymap = Keymap::preset(name).unwrap();
+            assert_eq!(keymap.name(), name);
+            assert!(keymap.get_bindings(Action::Quit).next().is_some());
+        }
+        assert!(Keymap::preset("emacs").is_none());
+    }
+}
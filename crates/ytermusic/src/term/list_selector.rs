@@ -0,0 +1,99 @@
+use ratatui::{
+    Frame,
+    crossterm::event::{MouseButton, MouseEvent, MouseEventKind},
+    layout::{Position, Rect},
+    widgets::{Block, List, ListItem, ListState},
+};
+
+use crate::{config::get_config, term::keymap::Action};
+
+/// Scrollable list used by the screens, it keeps the selection and finds the clicked items
+#[derive(Default)]
+pub struct ListSelector {
+    state: ListState,
+    len: usize,
+    /// Area of the last render, the clicks are mapped to the items with it
+    area: Rect,
+}
+
+impl ListSelector {
+    pub fn selected(&self) -> Option<usize> {
+        self.state.selected().filter(|x| *x < self.len)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.state
+            .select(Some(index.min(self.len.saturating_sub(1))));
+    }
+
+    /// Keeps the selection in the list after its length changed
+    pub fn update(&mut self, len: usize) {
+        self.len = len;
+        if len == 0 {
+            self.state.select(None);
+        } else {
+            self.select(self.state.selected().unwrap_or(0));
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.len == 0 {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or(0) as isize;
+        self.select((selected + delta).max(0) as usize);
+    }
+
+    /// Number of items visible at once
+    fn page(&self) -> isize {
+        self.area.height.saturating_sub(2).max(1) as isize
+    }
+
+    /// Moves the selection, returns `false` when the action isn't a move
+    pub fn on_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::Up => self.move_by(-1),
+            Action::Down => self.move_by(1),
+            Action::PageUp => self.move_by(-self.page()),
+            Action::PageDown => self.move_by(self.page()),
+            Action::Top => self.select(0),
+            Action::Bottom => self.select(self.len),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Scrolls with the wheel, returns the index of the clicked item
+    pub fn on_mouse_press(&mut self, event: &MouseEvent) -> Option<usize> {
+        if !self.area.contains(Position::new(event.column, event.row)) {
+            return None;
+        }
+        match event.kind {
+            MouseEventKind::ScrollUp => self.move_by(-1),
+            MouseEventKind::ScrollDown => self.move_by(1),
+            MouseEventKind::Down(MouseButton::Left) => {
+                // The first and last rows are the borders of the block
+                let row = event.row.checked_sub(self.area.y + 1)?;
+                if row + 2 >= self.area.height {
+                    return None;
+                }
+                let index = self.state.offset() + row as usize;
+                if index < self.len {
+                    self.select(index);
+                    return Some(index);
+                }
+            }
+            _ => (),
+        }
+        None
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, block: Block, items: Vec<ListItem>) {
+        self.area = area;
+        self.update(items.len());
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(get_config().ui.theme.highlight_style());
+        frame.render_stateful_widget(list, area, &mut self.state);
+    }
+}
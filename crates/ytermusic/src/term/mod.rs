@@ -1,4 +1,55 @@
-use ytapi2::types::YoutubeMusicVideoRef;
+pub mod device_lost;
+pub mod help;
+pub mod keymap;
+pub mod list_selector;
+pub mod music_player;
+pub mod playlist;
+pub mod playlist_view;
+pub mod search;
+pub mod theme;
+
+use std::{io::stdout, sync::Arc, time::Duration};
+
+use flume::{Receiver, Sender};
+use log::info;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::{
+        event::{
+            self, DisableMouseCapture, EnableMouseCapture, Event, KeyEvent, KeyEventKind,
+            MouseEvent,
+        },
+        execute,
+    },
+};
+use ytapi2::{instance::YoutubeMusicInstance, types::YoutubeMusicVideoRef};
+
+use crate::{
+    config::get_config,
+    errors::ErrorCategory,
+    shutdown::{guard_shutdown, is_shutdown_sent, shutdown},
+    spawn_connection,
+    structures::queue::{Queue, QueueAction},
+    systems::{
+        control::{Controller, Request},
+        download::{DownloadStatus, spawn_download_system},
+        player::{PlayerAction, PlayerState},
+    },
+    tasks::library::spawn_library_task,
+    term::{
+        device_lost::DeviceLost,
+        help::Help,
+        keymap::{Action, Key},
+        music_player::MusicPlayer,
+        playlist::Chooser,
+        playlist_view::PlaylistView,
+        search::Search,
+        theme::Panel,
+    },
+};
+
+/// Time waited for a terminal event before the updates are checked again
+const EVENT_TIMEOUT: Duration = Duration::from_millis(50);
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -8,18 +59,239 @@ pub enum Screens {
     Search = 0x2,
     DeviceLost = 0x3,
     PlaylistViewer = 0x4,
+    Help = 0x5,
 }
 
 #[derive(Debug, Clone)]
 pub enum ManagerMessage {
-    Error(String, Box<Option<ManagerMessage>>),
+    /// An error with the message dispatched by the action offered to fix it
+    Error(ErrorCategory, String, Box<Option<ManagerMessage>>),
     PassTo(Screens, Box<ManagerMessage>),
     Inspect(String, Screens, Vec<YoutubeMusicVideoRef>),
     ChangeState(Screens),
     SearchFrom(Screens),
     PlayerFrom(Screens),
     PlaylistFrom(Screens),
+    HelpFrom(Screens),
+    /// Reopens the audio device, after it was lost
     RestartPlayer,
+    /// Connects to YouTube Music, reading the cookies or `headers.txt` again
+    Connect,
+    Connected(Arc<YoutubeMusicInstance>),
     Quit,
     AddElementToChooser((String, Vec<YoutubeMusicVideoRef>)),
+    Download(YoutubeMusicVideoRef, DownloadStatus),
+    PlayerState(PlayerState),
+    /// A change of the queue applied by the player, with the queue after it
+    Queue(QueueAction, Box<Queue>),
+    /// Videos found online for a query
+    SearchResults(String, Vec<YoutubeMusicVideoRef>),
+    /// Completions of the query, sent before its results
+    SearchSuggestions(String, Vec<String>),
+}
+
+pub enum EventResponse {
+    Message(Vec<ManagerMessage>),
+    None,
+}
+
+impl EventResponse {
+    pub fn message(message: ManagerMessage) -> Self {
+        Self::Message(vec![message])
+    }
+}
+
+/// Shortcuts shown at the bottom of the lists, unless the hints are hidden
+pub fn hints(hints: &[(Action, &str)]) -> String {
+    let config = get_config();
+    if config.ui.shows(Panel::Hints) {
+        config.keymap.hints(hints)
+    } else {
+        String::new()
+    }
+}
+
+pub trait Screen {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse;
+    /// Handles the action bound to the pressed keys in the keymap
+    fn on_action(&mut self, action: Action) -> EventResponse;
+    /// Lets a screen take the keys as typed text before the keymap, `None` leaves the key to it
+    fn on_text_input(&mut self, _key: KeyEvent) -> Option<EventResponse> {
+        None
+    }
+    fn render(&mut self, frame: &mut Frame);
+    /// Handles the messages the manager routes to this screen
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse;
+}
+
+/// Owns the screens, draws the current one and routes the events and messages to them
+pub struct Manager {
+    player: Sender<PlayerAction>,
+    updater: Sender<ManagerMessage>,
+    instance: Option<Arc<YoutubeMusicInstance>>,
+    music_player: MusicPlayer,
+    chooser: Chooser,
+    search: Search,
+    device_lost: DeviceLost,
+    playlist_view: PlaylistView,
+    help: Help,
+    current_screen: Screens,
+    /// Keys of a binding being typed, such as the first `g` of `g g`
+    pending_keys: Vec<Key>,
+    /// Answers the commands of the control socket
+    controller: Controller,
+    requests: Receiver<Request>,
+}
+
+impl Manager {
+    pub fn new(
+        player: Sender<PlayerAction>,
+        updater: Sender<ManagerMessage>,
+        requests: Receiver<Request>,
+    ) -> Self {
+        Self {
+            controller: Controller::new(player.clone()),
+            requests,
+            music_player: MusicPlayer::new(player.clone()),
+            chooser: Chooser::new(player.clone()),
+            search: Search::new(player.clone(), updater.clone()),
+            device_lost: DeviceLost::default(),
+            playlist_view: PlaylistView::new(player.clone()),
+            help: Help::default(),
+            player,
+            updater,
+            instance: None,
+            current_screen: Screens::MusicPlayer,
+            pending_keys: Vec::new(),
+        }
+    }
+
+    fn get_screen(&mut self, screen: Screens) -> &mut dyn Screen {
+        match screen {
+            Screens::MusicPlayer => &mut self.music_player,
+            Screens::Playlist => &mut self.chooser,
+            Screens::Search => &mut self.search,
+            Screens::DeviceLost => &mut self.device_lost,
+            Screens::PlaylistViewer => &mut self.playlist_view,
+            Screens::Help => &mut self.help,
+        }
+    }
+
+    fn handle_response(&mut self, response: EventResponse) {
+        if let EventResponse::Message(messages) = response {
+            for message in messages {
+                self.handle_manager_message(message);
+            }
+        }
+    }
+
+    fn pass_to(&mut self, screen: Screens, message: ManagerMessage) {
+        let response = self.get_screen(screen).handle_global_message(message);
+        self.handle_response(response);
+    }
+
+    pub fn handle_manager_message(&mut self, message: ManagerMessage) {
+        self.controller.observe(&message);
+        match message {
+            ManagerMessage::PassTo(screen, message) => self.pass_to(screen, *message),
+            ManagerMessage::ChangeState(screen) => self.current_screen = screen,
+            ManagerMessage::Quit => shutdown(),
+            ManagerMessage::RestartPlayer => self.player.send(PlayerAction::ReopenDevice).unwrap(),
+            ManagerMessage::Connect => spawn_connection(self.updater.clone()),
+            ManagerMessage::Connected(instance) => self.connected(instance),
+            ManagerMessage::SearchFrom(_)
+            | ManagerMessage::SearchResults(..)
+            | ManagerMessage::SearchSuggestions(..) => self.pass_to(Screens::Search, message),
+            ManagerMessage::PlaylistFrom(_) | ManagerMessage::AddElementToChooser(_) => {
+                self.pass_to(Screens::Playlist, message)
+            }
+            ManagerMessage::Inspect(..) => self.pass_to(Screens::PlaylistViewer, message),
+            ManagerMessage::HelpFrom(_) => self.pass_to(Screens::Help, message),
+            ManagerMessage::Error(..) => self.pass_to(Screens::DeviceLost, message),
+            ManagerMessage::PlayerFrom(_)
+            | ManagerMessage::PlayerState(_)
+            | ManagerMessage::Download(..)
+            | ManagerMessage::Queue(..) => self.pass_to(Screens::MusicPlayer, message),
+        }
+    }
+
+    fn connected(&mut self, instance: Arc<YoutubeMusicInstance>) {
+        // The download workers keep the first instance, the streams don't depend on the account
+        if self.instance.is_none() {
+            spawn_download_system(instance.clone(), self.updater.clone());
+        }
+        spawn_library_task(instance.clone(), self.updater.clone());
+        self.instance = Some(instance.clone());
+        self.pass_to(Screens::Search, ManagerMessage::Connected(instance));
+        if self.current_screen == Screens::DeviceLost {
+            self.current_screen = Screens::MusicPlayer;
+        }
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent) -> EventResponse {
+        if self.pending_keys.is_empty()
+            && let Some(response) = self.get_screen(self.current_screen).on_text_input(key)
+        {
+            return response;
+        }
+        let Some(action) = get_config()
+            .keymap
+            .resolve(&mut self.pending_keys, key.into())
+        else {
+            return EventResponse::None;
+        };
+        match action {
+            Action::Quit => EventResponse::message(ManagerMessage::Quit),
+            Action::Help => EventResponse::message(ManagerMessage::HelpFrom(self.current_screen)),
+            action => self.get_screen(self.current_screen).on_action(action),
+        }
+    }
+
+    /// Runs the interface until the shutdown, the terminal is restored before returning
+    pub fn run(&mut self, updater: &Receiver<ManagerMessage>) -> std::io::Result<()> {
+        // The process must not exit before the terminal is restored
+        let guard = guard_shutdown();
+        // Also restores the terminal when panicking
+        let mut terminal = ratatui::try_init()?;
+        execute!(stdout(), EnableMouseCapture)?;
+        let result = self.event_loop(&mut terminal, updater);
+        restore_terminal()?;
+        info!("Terminal restored");
+        drop(guard);
+        result
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        updater: &Receiver<ManagerMessage>,
+    ) -> std::io::Result<()> {
+        while !is_shutdown_sent() {
+            for message in updater.try_iter() {
+                self.handle_manager_message(message);
+            }
+            for (command, response) in self.requests.try_iter() {
+                self.controller.handle_command(command, response);
+            }
+            terminal.draw(|frame| self.get_screen(self.current_screen).render(frame))?;
+
+            if !event::poll(EVENT_TIMEOUT)? {
+                continue;
+            }
+            let response = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Release => continue,
+                Event::Key(key) => self.on_key_press(key),
+                Event::Mouse(event) => self.get_screen(self.current_screen).on_mouse_press(event),
+                _ => continue,
+            };
+            self.handle_response(response);
+        }
+        Ok(())
+    }
+}
+
+/// Leaves the alternate screen and the raw mode
+fn restore_terminal() -> std::io::Result<()> {
+    execute!(stdout(), DisableMouseCapture)?;
+    ratatui::try_restore()
 }
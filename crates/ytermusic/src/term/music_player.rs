@@ -0,0 +1,282 @@
+use std::{collections::HashMap, time::Duration};
+
+use flume::Sender;
+use ratatui::{
+    Frame,
+    crossterm::event::MouseEvent,
+    layout::{Constraint, Layout},
+    style::Style,
+    text::{Line, Span},
+    widgets::{Gauge, ListItem, Paragraph},
+};
+
+use crate::{
+    config::get_config,
+    structures::queue::{Queue, QueueAction, RepeatMode},
+    systems::{
+        download::DownloadStatus,
+        player::{PlayerAction, PlayerState},
+    },
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector, theme::Panel,
+    },
+};
+
+/// The queue, the progress of the current video and the status of the player
+pub struct MusicPlayer {
+    player: Sender<PlayerAction>,
+    state: Option<PlayerState>,
+    queue: Queue,
+    /// Downloads not done yet, by video id
+    downloads: HashMap<String, DownloadStatus>,
+    list: ListSelector,
+}
+
+impl MusicPlayer {
+    pub fn new(player: Sender<PlayerAction>) -> Self {
+        Self {
+            player,
+            state: None,
+            queue: Queue::default(),
+            downloads: HashMap::new(),
+            list: ListSelector::default(),
+        }
+    }
+
+    fn send(&self, action: PlayerAction) -> EventResponse {
+        self.player.send(action).unwrap();
+        EventResponse::None
+    }
+
+    fn edit_selected(&self, action: impl FnOnce(usize) -> QueueAction) -> EventResponse {
+        match self.list.selected() {
+            Some(index) => self.send(PlayerAction::Edit(action(index))),
+            None => EventResponse::None,
+        }
+    }
+
+    fn get_download_label(&self, video_id: &str) -> Option<String> {
+        Some(match self.downloads.get(video_id)? {
+            DownloadStatus::Queued => "queued".to_string(),
+            DownloadStatus::Downloading(progress) => format!("{progress}%"),
+            DownloadStatus::Retrying(attempt) => format!("retry {attempt}"),
+            DownloadStatus::Done => return None,
+            DownloadStatus::Failed(_) => "failed".to_string(),
+        })
+    }
+
+    fn render_queue(&mut self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let theme = &get_config().ui.theme;
+        let current = self.queue.current_index();
+        let items = self
+            .queue
+            .videos()
+            .iter()
+            .enumerate()
+            .map(|(index, video)| {
+                let style = match current {
+                    Some(x) if x == index => theme.current_style(),
+                    Some(x) if index < x => Style::default().fg(theme.played),
+                    _ => Style::default(),
+                };
+                let mut spans = vec![
+                    Span::raw(if current == Some(index) { "▶ " } else { "  " }),
+                    Span::raw(video.to_string()),
+                ];
+                if let Some(label) = self.get_download_label(&video.video_id) {
+                    spans.push(Span::styled(
+                        format!(" [{label}]"),
+                        Style::default().fg(theme.download),
+                    ));
+                }
+                ListItem::new(Line::from(spans)).style(style)
+            })
+            .collect();
+        let block = theme
+            .block()
+            .title(format!(" Queue ({}) ", self.queue.videos().len()))
+            .title_bottom(hints(&[
+                (Action::PlayPause, "play/pause"),
+                (Action::Search, "search"),
+                (Action::Back, "playlists"),
+                (Action::Help, "help"),
+            ]));
+        self.list.render(frame, area, block, items);
+    }
+
+    fn render_progress(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let state = self.state.as_ref();
+        let video = state.and_then(|x| x.video.as_ref());
+        let title = video.map_or(" Nothing is playing ".to_string(), |x| format!(" {x} "));
+        let position = state.map_or(Duration::ZERO, |x| x.position);
+        let duration = state.and_then(|x| x.duration);
+
+        let label = match (state, video) {
+            (Some(state), Some(video)) if state.loading => {
+                match self.get_download_label(&video.video_id) {
+                    Some(label) => format!("Downloading {label}"),
+                    None => "Loading".to_string(),
+                }
+            }
+            _ => match duration {
+                Some(duration) => {
+                    format!("{} / {}", format_time(position), format_time(duration))
+                }
+                None => format_time(position),
+            },
+        };
+        let ratio = duration
+            .filter(|x| !x.is_zero())
+            .map_or(0., |x| position.as_secs_f64() / x.as_secs_f64());
+        let theme = &get_config().ui.theme;
+        let gauge = Gauge::default()
+            .block(theme.block().title(title))
+            .gauge_style(theme.progress_style())
+            .ratio(ratio.clamp(0., 1.))
+            .label(label);
+        frame.render_widget(gauge, area);
+    }
+
+    fn render_status(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let state = self.state.as_ref();
+        let playing = match state {
+            Some(x) if x.video.is_none() => "■ Stopped",
+            Some(x) if x.paused => "⏸ Paused",
+            Some(_) => "▶ Playing",
+            None => "■ Stopped",
+        };
+        let repeat = match self.queue.repeat() {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "one",
+            RepeatMode::All => "all",
+        };
+        let shuffle = if self.queue.is_shuffled() {
+            "on"
+        } else {
+            "off"
+        };
+        let downloads = self
+            .downloads
+            .values()
+            .filter(|x| !matches!(x, DownloadStatus::Failed(_)))
+            .count();
+        let status = format!(
+            " {playing} | Volume {}% | Repeat {repeat} | Shuffle {shuffle} | Downloads {downloads}",
+            state.map_or(0, |x| x.volume)
+        );
+        let theme = &get_config().ui.theme;
+        frame.render_widget(
+            Paragraph::new(status).style(Style::default().fg(theme.text)),
+            area,
+        );
+    }
+}
+
+impl Screen for MusicPlayer {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        match self.list.on_mouse_press(&event) {
+            Some(index) => self.send(PlayerAction::Edit(QueueAction::Select(index))),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back => {
+                EventResponse::message(ManagerMessage::PlaylistFrom(Screens::MusicPlayer))
+            }
+            Action::Search => {
+                EventResponse::message(ManagerMessage::SearchFrom(Screens::MusicPlayer))
+            }
+            Action::PlayPause => self.send(PlayerAction::PlayPause),
+            Action::Select => self.edit_selected(QueueAction::Select),
+            Action::Remove => self.edit_selected(QueueAction::Remove),
+            Action::Shuffle if self.queue.is_shuffled() => {
+                self.send(PlayerAction::Edit(QueueAction::Unshuffle))
+            }
+            Action::Shuffle => self.send(PlayerAction::Edit(QueueAction::Shuffle)),
+            Action::Repeat => {
+                let repeat = match self.queue.repeat() {
+                    RepeatMode::Off => RepeatMode::All,
+                    RepeatMode::All => RepeatMode::One,
+                    RepeatMode::One => RepeatMode::Off,
+                };
+                self.send(PlayerAction::Edit(QueueAction::SetRepeat(repeat)))
+            }
+            Action::Next => self.send(PlayerAction::Next),
+            Action::SeekForward => self.send(PlayerAction::Forward),
+            Action::Previous => self.send(PlayerAction::Previous),
+            Action::SeekBackward => self.send(PlayerAction::Backward),
+            Action::VolumeUp => self.send(PlayerAction::VolumeUp),
+            Action::VolumeDown => self.send(PlayerAction::VolumeDown),
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let ui = &get_config().ui;
+        let panels = [
+            (Panel::Queue, Constraint::Min(3)),
+            (Panel::Progress, Constraint::Length(3)),
+            (Panel::Status, Constraint::Length(1)),
+        ]
+        .into_iter()
+        .filter(|(panel, _)| ui.shows(*panel))
+        .collect::<Vec<_>>();
+        let areas = Layout::vertical(panels.iter().map(|(_, x)| *x)).split(frame.area());
+        for ((panel, _), area) in panels.into_iter().zip(areas.iter()) {
+            match panel {
+                Panel::Queue => self.render_queue(frame, *area),
+                Panel::Progress => self.render_progress(frame, *area),
+                Panel::Status => self.render_status(frame, *area),
+                Panel::Hints => (),
+            }
+        }
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        match message {
+            ManagerMessage::PlayerFrom(_) => {
+                return EventResponse::message(ManagerMessage::ChangeState(Screens::MusicPlayer));
+            }
+            ManagerMessage::PlayerState(state) => self.state = Some(state),
+            ManagerMessage::Queue(_, queue) => {
+                let follow = self.list.selected().is_none()
+                    || self.list.selected() == self.queue.current_index();
+                self.queue = *queue;
+                self.list.update(self.queue.videos().len());
+                // The selection follows the playback unless the user moved it
+                if follow && let Some(current) = self.queue.current_index() {
+                    self.list.select(current);
+                }
+            }
+            ManagerMessage::Download(video, DownloadStatus::Done) => {
+                self.downloads.remove(&video.video_id);
+            }
+            ManagerMessage::Download(video, status) => {
+                self.downloads.insert(video.video_id, status);
+            }
+            _ => (),
+        }
+        EventResponse::None
+    }
+}
+
+/// Formats a duration as `m:ss`, or `h:mm:ss` past an hour
+pub fn format_time(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,115 @@
+use flume::Sender;
+use ratatui::{Frame, crossterm::event::MouseEvent, widgets::ListItem};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::{
+    config::get_config,
+    database::DATABASE,
+    structures::queue::QueueAction,
+    systems::player::PlayerAction,
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector,
+    },
+};
+
+/// Lists the local musics and the playlists of the library, selecting one replaces the queue
+pub struct Chooser {
+    player: Sender<PlayerAction>,
+    playlists: Vec<(String, Vec<YoutubeMusicVideoRef>)>,
+    list: ListSelector,
+    /// Screen opened when leaving the chooser
+    previous: Screens,
+}
+
+impl Chooser {
+    pub fn new(player: Sender<PlayerAction>) -> Self {
+        Self {
+            player,
+            playlists: vec![("Local musics".to_string(), DATABASE.videos())],
+            list: ListSelector::default(),
+            previous: Screens::MusicPlayer,
+        }
+    }
+
+    fn play(&self, index: usize) -> EventResponse {
+        let (_, videos) = &self.playlists[index];
+        self.player
+            .send(PlayerAction::Edit(QueueAction::Clear))
+            .unwrap();
+        self.player
+            .send(PlayerAction::Queue(videos.clone()))
+            .unwrap();
+        EventResponse::message(ManagerMessage::ChangeState(Screens::MusicPlayer))
+    }
+}
+
+impl Screen for Chooser {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        match self.list.on_mouse_press(&event) {
+            Some(index) => self.play(index),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back => EventResponse::message(ManagerMessage::ChangeState(self.previous)),
+            Action::Search => EventResponse::message(ManagerMessage::SearchFrom(Screens::Playlist)),
+            Action::Select => match self.list.selected() {
+                Some(index) => self.play(index),
+                None => EventResponse::None,
+            },
+            Action::SeekForward => match self.list.selected() {
+                Some(index) => {
+                    let (name, videos) = self.playlists[index].clone();
+                    EventResponse::message(ManagerMessage::Inspect(name, Screens::Playlist, videos))
+                }
+                None => EventResponse::None,
+            },
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let items = self
+            .playlists
+            .iter()
+            .map(|(name, videos)| ListItem::new(format!("{name} ({} musics)", videos.len())))
+            .collect();
+        let block = get_config()
+            .ui
+            .theme
+            .block()
+            .title(" Choose a playlist ")
+            .title_bottom(hints(&[
+                (Action::Select, "play"),
+                (Action::SeekForward, "open"),
+                (Action::Search, "search"),
+                (Action::Back, "back"),
+            ]));
+        self.list.render(frame, frame.area(), block, items);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        match message {
+            ManagerMessage::PlaylistFrom(screen) => {
+                self.previous = screen;
+                return EventResponse::message(ManagerMessage::ChangeState(Screens::Playlist));
+            }
+            ManagerMessage::AddElementToChooser((name, videos)) => {
+                // A playlist sent again replaces the previous version
+                match self.playlists.iter_mut().find(|(x, _)| *x == name) {
+                    Some(playlist) => playlist.1 = videos,
+                    None => self.playlists.push((name, videos)),
+                }
+                self.list.update(self.playlists.len());
+            }
+            _ => (),
+        }
+        EventResponse::None
+    }
+}
@@ -0,0 +1,101 @@
+use flume::Sender;
+use ratatui::{Frame, crossterm::event::MouseEvent, widgets::ListItem};
+use ytapi2::types::YoutubeMusicVideoRef;
+
+use crate::{
+    config::get_config,
+    structures::queue::QueueAction,
+    systems::player::PlayerAction,
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector,
+    },
+};
+
+/// Shows the videos of a playlist, the playback can start from any of them
+pub struct PlaylistView {
+    player: Sender<PlayerAction>,
+    title: String,
+    videos: Vec<YoutubeMusicVideoRef>,
+    list: ListSelector,
+    /// Screen which opened the playlist, it is restored when leaving
+    previous: Screens,
+}
+
+impl PlaylistView {
+    pub fn new(player: Sender<PlayerAction>) -> Self {
+        Self {
+            player,
+            title: String::new(),
+            videos: Vec::new(),
+            list: ListSelector::default(),
+            previous: Screens::Playlist,
+        }
+    }
+
+    fn play(&self, index: usize) -> EventResponse {
+        for action in [
+            QueueAction::Clear,
+            QueueAction::Append(self.videos.clone()),
+            QueueAction::Select(index),
+        ] {
+            self.player.send(PlayerAction::Edit(action)).unwrap();
+        }
+        EventResponse::message(ManagerMessage::ChangeState(Screens::MusicPlayer))
+    }
+}
+
+impl Screen for PlaylistView {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        match self.list.on_mouse_press(&event) {
+            Some(index) => self.play(index),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back | Action::SeekBackward => {
+                EventResponse::message(ManagerMessage::ChangeState(self.previous))
+            }
+            Action::Select => match self.list.selected() {
+                Some(index) => self.play(index),
+                None => EventResponse::None,
+            },
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let items = self
+            .videos
+            .iter()
+            .map(|video| ListItem::new(video.to_string()))
+            .collect();
+        let block = get_config()
+            .ui
+            .theme
+            .block()
+            .title(format!(" {} ", self.title))
+            .title_bottom(hints(&[
+                (Action::Select, "play from here"),
+                (Action::Back, "back"),
+            ]));
+        self.list.render(frame, frame.area(), block, items);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        if let ManagerMessage::Inspect(title, screen, videos) = message {
+            self.title = title;
+            self.previous = screen;
+            self.videos = videos;
+            self.list.update(self.videos.len());
+            self.list.select(0);
+            return EventResponse::message(ManagerMessage::ChangeState(Screens::PlaylistViewer));
+        }
+        EventResponse::None
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use flume::Sender;
+use log::warn;
+use ratatui::{
+    Frame,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEvent},
+    layout::{Constraint, Layout},
+    style::Style,
+    text::{Line, Span},
+    widgets::{ListItem, Paragraph},
+};
+use ytapi2::{instance::YoutubeMusicInstance, types::YoutubeMusicVideoRef};
+
+use crate::{
+    config::get_config,
+    database::search_local,
+    run_service,
+    systems::player::PlayerAction,
+    term::{
+        EventResponse, ManagerMessage, Screen, Screens, hints, keymap::Action,
+        list_selector::ListSelector,
+    },
+};
+
+/// Time without typing before the query is sent to YouTube Music
+const SEARCH_DELAY: Duration = Duration::from_millis(400);
+
+/// Suggestions shown under the input
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Searches the local musics while typing, then YouTube Music with its suggestions once the
+/// typing stops
+pub struct Search {
+    player: Sender<PlayerAction>,
+    updater: Sender<ManagerMessage>,
+    /// Only the local musics are searched until connected
+    instance: Option<Arc<YoutubeMusicInstance>>,
+    text: String,
+    local: Vec<YoutubeMusicVideoRef>,
+    online: Vec<YoutubeMusicVideoRef>,
+    /// Completions of the text by YouTube Music
+    suggestions: Vec<String>,
+    list: ListSelector,
+    /// Screen opened when leaving the search
+    previous: Screens,
+    /// Incremented at each change of the text, so only the last query is sent
+    generation: Arc<AtomicUsize>,
+}
+
+impl Search {
+    pub fn new(player: Sender<PlayerAction>, updater: Sender<ManagerMessage>) -> Self {
+        Self {
+            player,
+            updater,
+            instance: None,
+            text: String::new(),
+            local: Vec::new(),
+            online: Vec::new(),
+            suggestions: Vec::new(),
+            list: ListSelector::default(),
+            previous: Screens::MusicPlayer,
+            generation: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn results(&self) -> impl Iterator<Item = &YoutubeMusicVideoRef> {
+        self.local.iter().chain(self.online.iter())
+    }
+
+    fn search(&mut self) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.online.clear();
+        self.local = search_local(&self.text);
+        self.list.update(self.local.len());
+
+        let Some(instance) = self.instance.clone() else {
+            return;
+        };
+        if self.text.trim().is_empty() {
+            self.suggestions.clear();
+            return;
+        }
+        let query = self.text.clone();
+        let current = self.generation.clone();
+        let updater = self.updater.clone();
+        run_service(async move {
+            tokio::time::sleep(SEARCH_DELAY).await;
+            if current.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            let (suggestions, results) = tokio::join!(
+                instance.get_search_suggestions(&query),
+                instance.search(&query)
+            );
+            match suggestions {
+                Ok(suggestions) => updater
+                    .send(ManagerMessage::SearchSuggestions(
+                        query.clone(),
+                        suggestions.suggestions,
+                    ))
+                    .unwrap(),
+                Err(e) => warn!("Can't get the suggestions of `{query}`: {e:?}"),
+            }
+            match results {
+                Ok(results) => {
+                    let videos = results.songs.into_iter().chain(results.videos).collect();
+                    updater
+                        .send(ManagerMessage::SearchResults(query, videos))
+                        .unwrap();
+                }
+                Err(e) => warn!("Can't search `{query}`: {e:?}"),
+            }
+        });
+    }
+
+    fn play(&self, index: usize) -> EventResponse {
+        let Some(video) = self.results().nth(index) else {
+            return EventResponse::None;
+        };
+        self.player.send(PlayerAction::Play(video.clone())).unwrap();
+        EventResponse::message(ManagerMessage::ChangeState(Screens::MusicPlayer))
+    }
+}
+
+impl Screen for Search {
+    fn on_mouse_press(&mut self, event: MouseEvent) -> EventResponse {
+        match self.list.on_mouse_press(&event) {
+            Some(index) => self.play(index),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_action(&mut self, action: Action) -> EventResponse {
+        match action {
+            Action::Back => EventResponse::message(ManagerMessage::ChangeState(self.previous)),
+            Action::Select => match self.list.selected() {
+                Some(index) => self.play(index),
+                None => EventResponse::None,
+            },
+            _ => {
+                self.list.on_action(action);
+                EventResponse::None
+            }
+        }
+    }
+
+    fn on_text_input(&mut self, key: KeyEvent) -> Option<EventResponse> {
+        match key.code {
+            KeyCode::Backspace => {
+                if self.text.pop().is_some() {
+                    self.search();
+                }
+            }
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.text.push(c);
+                self.search();
+            }
+            _ => return None,
+        }
+        Some(EventResponse::None)
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let suggestions = self.suggestions.len().min(MAX_SUGGESTIONS) as u16;
+        let [input, suggestions_area, results] = Layout::vertical([
+            Constraint::Length(3),
+            // The borders are hidden with the suggestions
+            Constraint::Length(if suggestions == 0 { 0 } else { suggestions + 2 }),
+            Constraint::Min(3),
+        ])
+        .areas(frame.area());
+        let theme = &get_config().ui.theme;
+        frame.render_widget(
+            Paragraph::new(self.text.as_str()).block(theme.block().title(" Search ")),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.text.chars().count() as u16, input.y + 1));
+        if suggestions > 0 {
+            let lines = self
+                .suggestions
+                .iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|x| Line::raw(x.as_str()))
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                Paragraph::new(lines).block(theme.block().title(" Suggestions ")),
+                suggestions_area,
+            );
+        }
+
+        let local = Style::default().fg(theme.local);
+        let items = self
+            .results()
+            .enumerate()
+            .map(|(index, video)| {
+                let mut spans = vec![Span::raw(video.to_string())];
+                if index < self.local.len() {
+                    spans.push(Span::styled(" [local]", local));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let block = theme
+            .block()
+            .title(" Results ")
+            .title_bottom(hints(&[(Action::Select, "play"), (Action::Back, "back")]));
+        self.list.render(frame, results, block, items);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        match message {
+            ManagerMessage::SearchFrom(screen) => {
+                self.previous = screen;
+                return EventResponse::message(ManagerMessage::ChangeState(Screens::Search));
+            }
+            ManagerMessage::Connected(instance) => self.instance = Some(instance),
+            ManagerMessage::SearchSuggestions(query, suggestions) if query == self.text => {
+                self.suggestions = suggestions;
+            }
+            ManagerMessage::SearchResults(query, videos) if query == self.text => {
+                self.online = videos
+                    .into_iter()
+                    .filter(|x| !self.local.iter().any(|y| y.video_id == x.video_id))
+                    .collect();
+                self.list.update(self.local.len() + self.online.len());
+            }
+            _ => (),
+        }
+        EventResponse::None
+    }
+}
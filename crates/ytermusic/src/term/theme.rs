@@ -0,0 +1,176 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    widgets::Block,
+};
+
+/// Colors of the screens, the built-in palettes can be extended by the theme files
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub text: Color,
+    pub border: Color,
+    /// Background of the selected item of the lists
+    pub highlight: Color,
+    pub highlight_text: Color,
+    /// The video being played in the queue
+    pub current: Color,
+    /// The videos already played in the queue
+    pub played: Color,
+    pub download: Color,
+    pub progress: Color,
+    pub progress_background: Color,
+    pub error: Color,
+    /// Label of the local musics in the search
+    pub local: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub const PRESETS: [&str; 4] = ["dark", "light", "high-contrast", "16-color"];
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "16-color" => Some(Self::sixteen_colors()),
+            _ => None,
+        }
+    }
+
+    fn dark() -> Self {
+        Self {
+            text: Color::Reset,
+            border: Color::Indexed(245),
+            highlight: Color::Indexed(238),
+            highlight_text: Color::Indexed(255),
+            current: Color::Indexed(114),
+            played: Color::Indexed(243),
+            download: Color::Indexed(221),
+            progress: Color::Indexed(114),
+            progress_background: Color::Indexed(236),
+            error: Color::Indexed(203),
+            local: Color::Indexed(114),
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            text: Color::Reset,
+            border: Color::Indexed(244),
+            highlight: Color::Indexed(253),
+            highlight_text: Color::Indexed(232),
+            current: Color::Indexed(28),
+            played: Color::Indexed(246),
+            download: Color::Indexed(130),
+            progress: Color::Indexed(28),
+            progress_background: Color::Indexed(252),
+            error: Color::Indexed(160),
+            local: Color::Indexed(28),
+        }
+    }
+
+    fn high_contrast() -> Self {
+        Self {
+            text: Color::White,
+            border: Color::White,
+            highlight: Color::Yellow,
+            highlight_text: Color::Black,
+            current: Color::LightCyan,
+            played: Color::Gray,
+            download: Color::LightYellow,
+            progress: Color::White,
+            progress_background: Color::Black,
+            error: Color::LightRed,
+            local: Color::LightGreen,
+        }
+    }
+
+    /// For the terminals without 256 colors
+    fn sixteen_colors() -> Self {
+        Self {
+            text: Color::Reset,
+            border: Color::Reset,
+            highlight: Color::DarkGray,
+            highlight_text: Color::Reset,
+            current: Color::Green,
+            played: Color::DarkGray,
+            download: Color::Yellow,
+            progress: Color::Green,
+            progress_background: Color::Black,
+            error: Color::Red,
+            local: Color::Green,
+        }
+    }
+
+    pub fn block(&self) -> Block<'static> {
+        Block::bordered()
+            .style(Style::default().fg(self.text))
+            .border_style(Style::default().fg(self.border))
+    }
+
+    pub fn highlight_style(&self) -> Style {
+        Style::default()
+            .fg(self.highlight_text)
+            .bg(self.highlight)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn current_style(&self) -> Style {
+        Style::default()
+            .fg(self.current)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    pub fn progress_style(&self) -> Style {
+        Style::default()
+            .fg(self.progress)
+            .bg(self.progress_background)
+    }
+
+    /// Sets the colors by name, for the theme files
+    pub fn colors_mut(&mut self) -> [(&'static str, &mut Color); 11] {
+        [
+            ("text", &mut self.text),
+            ("border", &mut self.border),
+            ("highlight", &mut self.highlight),
+            ("highlight_text", &mut self.highlight_text),
+            ("current", &mut self.current),
+            ("played", &mut self.played),
+            ("download", &mut self.download),
+            ("progress", &mut self.progress),
+            ("progress_background", &mut self.progress_background),
+            ("error", &mut self.error),
+            ("local", &mut self.local),
+        ]
+    }
+}
+
+/// Parts of the screens that can be hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// The queue of the player
+    Queue,
+    /// The progress bar of the player
+    Progress,
+    /// The status line of the player
+    Status,
+    /// The shortcuts at the bottom of the lists
+    Hints,
+}
+
+impl Panel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "queue" => Some(Panel::Queue),
+            "progress" => Some(Panel::Progress),
+            "status" => Some(Panel::Status),
+            "hints" => Some(Panel::Hints),
+            _ => None,
+        }
+    }
+}